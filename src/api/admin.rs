@@ -0,0 +1,282 @@
+// rotas de administração: ver quem está ouvindo, derrubar (e banir) ouvintes e emitir URLs assinadas
+
+use std::{net::IpAddr, time::{Duration, SystemTime}};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    response::status::Custom,
+    serde::json::Json,
+    State,
+};
+use serde::Serialize;
+use tracing::info;
+use web_radio::objects::radio::Radio;
+
+use crate::{
+    config::{AdminConfig, TokenConfig},
+    output_stream::{
+        bans::{BanList, MAX_BAN_MINUTES},
+        flood::{FloodGuard, FloodRejections},
+        token::StreamSigner,
+    },
+    StationMap,
+};
+
+/// Guard das rotas `/admin`: exige `Authorization: Bearer <token>` com o token da configuração
+pub struct AdminAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, String> {
+        let expected = request
+            .rocket()
+            .state::<AdminConfig>()
+            .and_then(|admin| admin.token.as_deref());
+        let given = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match (expected, given) {
+            (Some(expected), Some(given)) if constant_time_eq(expected.as_bytes(), given.trim().as_bytes()) => {
+                Outcome::Success(AdminAuth)
+            }
+            _ => Outcome::Error((Status::Unauthorized, "token de admin inválido".to_owned())),
+        }
+    }
+}
+
+/// Comparação que não para no primeiro byte diferente, para não vazar o token pelo tempo de resposta
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Serialize)]
+pub struct ClientEntry {
+    pub id: usize,
+    pub remote_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub listener_id: Option<String>,
+    pub bytes_sent: usize,
+    pub bits_per_second: f64,
+    pub connected_seconds: u64,
+}
+
+#[derive(Serialize)]
+pub struct MountClients {
+    pub codec: &'static str,
+    pub mount: String,
+    /// bitrate medido na saída do encoder, nos últimos segundos; nos codecs VBR, como o FLAC, é o único que há
+    pub bits_per_second: f64,
+    pub clients: Vec<ClientEntry>,
+}
+
+#[derive(Serialize)]
+pub struct StationClients {
+    pub name: String,
+    pub mounts: Vec<MountClients>,
+}
+
+#[derive(Serialize)]
+pub struct BanEntry {
+    pub address: IpAddr,
+    pub remaining_seconds: u64,
+}
+
+#[derive(Serialize)]
+pub struct ClientsResponse {
+    pub connections: usize,
+    pub stations: Vec<StationClients>,
+    pub bans: Vec<BanEntry>,
+    /// conexões recusadas pela proteção contra flood desde que o servidor subiu
+    pub rejections: FloodRejections,
+}
+
+#[get("/admin/clients")]
+pub fn list_clients(
+    _admin: AdminAuth,
+    radio: &State<Radio>,
+    state: &State<StationMap>,
+    bans: &State<BanList>,
+    flood: &State<FloodGuard>,
+) -> Json<ClientsResponse> {
+    let mut stations = Vec::new();
+
+    for name in radio.station_names() {
+        let Some(cytoplasm) = state.get(&name) else {
+            continue;
+        };
+        let subscribers = cytoplasm.station.lock().unwrap()._subscribers.clone();
+
+        let mounts = cytoplasm
+            .mounts()
+            .into_iter()
+            .map(|stream| {
+                let mut clients: Vec<ClientEntry> = stream
+                    .get_bandwidth_stats()
+                    .into_iter()
+                    .map(|(id, (bytes_sent, bits_per_second))| {
+                        let subscriber = subscribers.iter().find(|subscriber| subscriber.id == id);
+                        ClientEntry {
+                            id,
+                            remote_address: subscriber.and_then(|s| s.remote_address),
+                            user_agent: subscriber.and_then(|s| s.user_agent.clone()),
+                            listener_id: subscriber.and_then(|s| s.listener_id.clone()),
+                            bytes_sent,
+                            bits_per_second,
+                            connected_seconds: subscriber
+                                .and_then(|s| SystemTime::now().duration_since(s.connected_at).ok())
+                                .map_or(0, |age| age.as_secs()),
+                        }
+                    })
+                    .collect();
+                clients.sort_by_key(|client| client.id);
+
+                MountClients {
+                    codec: stream.name(),
+                    mount: stream.mount().to_owned(),
+                    bits_per_second: stream.counters().encoded.bits_per_second(),
+                    clients,
+                }
+            })
+            .collect();
+
+        stations.push(StationClients { name, mounts });
+    }
+
+    let bans = bans
+        .list()
+        .into_iter()
+        .map(|(address, remaining)| BanEntry {
+            address,
+            remaining_seconds: remaining.as_secs(),
+        })
+        .collect();
+
+    Json(ClientsResponse {
+        connections: radio.connection_count(),
+        stations,
+        bans,
+        rejections: flood.rejections(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct KickResponse {
+    pub id: usize,
+    pub mount: String,
+    /// endereço banido, se `ban_minutes` foi pedido e o endereço do cliente é conhecido
+    pub banned: Option<IpAddr>,
+    pub ban_minutes: Option<u64>,
+}
+
+/// Derruba o cliente; com `?ban_minutes=N`, o endereço dele fica sem poder abrir streams por N minutos
+/// (no máximo `MAX_BAN_MINUTES`)
+#[delete("/admin/clients/<id>?<ban_minutes>")]
+pub fn kick_client(
+    _admin: AdminAuth,
+    id: usize,
+    ban_minutes: Option<u64>,
+    state: &State<StationMap>,
+    bans: &State<BanList>,
+) -> Result<Json<KickResponse>, Custom<String>> {
+    if let Some(minutes) = ban_minutes.filter(|minutes| *minutes > MAX_BAN_MINUTES) {
+        return Err(Custom(
+            Status::BadRequest,
+            format!("ban_minutes: {} acima do máximo de {}", minutes, MAX_BAN_MINUTES),
+        ));
+    }
+    let (cytoplasm, stream) = state
+        .values()
+        .flat_map(|cytoplasm| cytoplasm.mounts().into_iter().map(move |stream| (cytoplasm, stream)))
+        .find(|(_, stream)| stream.list_clients().contains(&id))
+        .ok_or_else(|| Custom(Status::NotFound, format!("cliente {} não está conectado", id)))?;
+
+    // o endereço sai do Subscriber antes de derrubar, já que ele é removido da estação junto com o cliente
+    let address = cytoplasm
+        .station
+        .lock()
+        .unwrap()
+        ._subscribers
+        .iter()
+        .find(|subscriber| subscriber.id == id)
+        .and_then(|subscriber| subscriber.remote_address);
+
+    stream.terminate_client(id);
+
+    let banned = match (ban_minutes, address) {
+        (Some(minutes), Some(address)) if minutes > 0 => {
+            bans.ban(address, Duration::from_secs(minutes * 60));
+            info!(client_id = id, %address, minutes, "cliente derrubado e banido pelo admin");
+            Some(address)
+        }
+        _ => None,
+    };
+
+    Ok(Json(KickResponse {
+        id,
+        mount: stream.mount().to_owned(),
+        banned,
+        ban_minutes: banned.and(ban_minutes),
+    }))
+}
+
+/// validade padrão de um token emitido pelo admin, em minutos
+const DEFAULT_TOKEN_TTL_MINUTES: u64 = 60;
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub station: String,
+    pub token: String,
+    /// caminho do stream já com o token
+    pub url: String,
+    /// segundos desde a época unix
+    pub expires_at: u64,
+    pub listener: Option<String>,
+}
+
+/// Emite um token para os streams da estação, válido por `ttl_minutes` (padrão: 60), opcionalmente preso a um ouvinte
+#[post("/admin/stations/<name>/tokens?<ttl_minutes>&<listener>")]
+pub fn mint_token(
+    _admin: AdminAuth,
+    name: &str,
+    ttl_minutes: Option<u64>,
+    listener: Option<String>,
+    state: &State<StationMap>,
+    tokens: &State<TokenConfig>,
+) -> Result<Json<TokenResponse>, Custom<String>> {
+    if !state.contains_key(name) {
+        return Err(Custom(Status::NotFound, format!("estação desconhecida: {}", name)));
+    }
+    let signer = tokens.secret.as_deref().map(StreamSigner::new).ok_or_else(|| {
+        Custom(
+            Status::NotFound,
+            "emissão de tokens desligada: configure tokens.secret".to_owned(),
+        )
+    })?;
+
+    let ttl_minutes = ttl_minutes.unwrap_or(DEFAULT_TOKEN_TTL_MINUTES);
+    let ttl = ttl_minutes
+        .checked_mul(60)
+        .map(Duration::from_secs)
+        .ok_or_else(|| Custom(Status::BadRequest, format!("ttl_minutes: {} grande demais", ttl_minutes)))?;
+    let listener = listener.filter(|listener| !listener.is_empty());
+    let (token, claims) = signer.sign(name, ttl, listener);
+    info!(
+        station = name,
+        listener = ?claims.listener,
+        expires_at = claims.expires_at,
+        "token de stream emitido"
+    );
+
+    Ok(Json(TokenResponse {
+        url: format!("/station/{}?token={}", name, token),
+        station: claims.station,
+        token,
+        expires_at: claims.expires_at,
+        listener: claims.listener,
+    }))
+}
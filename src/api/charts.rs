@@ -0,0 +1,141 @@
+// paradas por estação e da rádio inteira, paginadas
+
+use rocket::{http::Status, response::status::Custom, serde::json::Json, time::OffsetDateTime, State};
+use serde::Serialize;
+use web_radio::objects::track::charts::{sort_chart, station_chart, ChartBy, ChartEntry, ChartWindow};
+
+use crate::{api::now_playing::TrackInfo, cytoplasm::cytoplasm::Cytoplasm, StationMap};
+
+const DEFAULT_PER_PAGE: usize = 20;
+const MAX_PER_PAGE: usize = 100;
+
+/// Os números de `likes`, `plays`, `peak_listeners` e `listener_hours` são só da janela pedida;
+/// `track.likes` é o total de sempre
+#[derive(Serialize)]
+pub struct ChartPosition {
+    pub position: usize,
+    pub station: String,
+    pub track: TrackInfo,
+    pub likes: usize,
+    pub plays: u64,
+    pub peak_listeners: usize,
+    pub listener_hours: f64,
+}
+
+#[derive(Serialize)]
+pub struct ChartResponse {
+    pub by: &'static str,
+    pub window: &'static str,
+    pub from: i64,
+    pub to: i64,
+    pub page: usize,
+    pub per_page: usize,
+    /// quantas faixas entraram na parada, somando todas as páginas
+    pub total: usize,
+    pub entries: Vec<ChartPosition>,
+}
+
+/// `by`: likes, plays, peak (padrão) ou listener_hours; `window`: today, 7d ou all (padrão)
+#[get("/station/<name>/charts?<by>&<window>&<page>&<per_page>")]
+pub fn station_charts(
+    name: &str,
+    by: Option<&str>,
+    window: Option<&str>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    state: &State<StationMap>,
+) -> Result<Json<ChartResponse>, Custom<String>> {
+    let cytoplasm = state
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+
+    chart(vec![(name, cytoplasm)], by, window, page, per_page).map(Json)
+}
+
+/// A mesma parada, juntando todas as estações
+#[get("/charts?<by>&<window>&<page>&<per_page>")]
+pub fn radio_charts(
+    by: Option<&str>,
+    window: Option<&str>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    state: &State<StationMap>,
+) -> Result<Json<ChartResponse>, Custom<String>> {
+    let stations = state
+        .iter()
+        .map(|(name, cytoplasm)| (name.as_str(), cytoplasm))
+        .collect();
+
+    chart(stations, by, window, page, per_page).map(Json)
+}
+
+fn chart(
+    stations: Vec<(&str, &Cytoplasm)>,
+    by: Option<&str>,
+    window: Option<&str>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+) -> Result<ChartResponse, Custom<String>> {
+    let by_name = by.unwrap_or("peak");
+    let by = ChartBy::from_name(by_name)
+        .ok_or_else(|| Custom(Status::BadRequest, format!("parada desconhecida: {}", by_name)))?;
+    let window_name = window.unwrap_or("all");
+    let window = ChartWindow::from_name(window_name)
+        .ok_or_else(|| Custom(Status::BadRequest, format!("janela desconhecida: {}", window_name)))?;
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let (from, to) = window.bounds(OffsetDateTime::now_utc());
+
+    let mut chart: Vec<ChartEntry> = Vec::new();
+    for (name, cytoplasm) in &stations {
+        let plays = cytoplasm
+            .plays
+            .range(from, to)
+            .map_err(|e| Custom(Status::InternalServerError, e))?;
+        let likes = cytoplasm.likes.lock().unwrap();
+        let station = cytoplasm.station.lock().unwrap();
+
+        // faixas que saíram da estação não entram na parada
+        chart.extend(
+            station_chart(name, &plays, &likes, from, to)
+                .into_iter()
+                .filter(|entry| station.tracks.iter().any(|track| track.source == entry.source)),
+        );
+    }
+    sort_chart(&mut chart, by);
+    let total = chart.len();
+
+    let entries = chart
+        .into_iter()
+        .enumerate()
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+        .filter_map(|(index, entry)| {
+            let (_, cytoplasm) = stations.iter().find(|(name, _)| *name == entry.station)?;
+            let likes = cytoplasm.likes.lock().unwrap();
+            let station = cytoplasm.station.lock().unwrap();
+            let track = station.tracks.iter().find(|track| track.source == entry.source)?;
+
+            Some(ChartPosition {
+                position: index + 1,
+                track: TrackInfo::new(track, &likes),
+                station: entry.station,
+                likes: entry.likes,
+                plays: entry.plays,
+                peak_listeners: entry.peak_listeners,
+                listener_hours: entry.listener_hours,
+            })
+        })
+        .collect();
+
+    Ok(ChartResponse {
+        by: by.name(),
+        window: window.name(),
+        from,
+        to,
+        page,
+        per_page,
+        total,
+        entries,
+    })
+}
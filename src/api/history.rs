@@ -0,0 +1,89 @@
+// o que tocou numa estação: um dia inteiro ou um intervalo de tempo
+
+use rocket::{
+    http::Status,
+    response::status::Custom,
+    serde::json::Json,
+    time::{Date, Month, OffsetDateTime},
+    State,
+};
+use serde::Serialize;
+use web_radio::objects::station::history::day_bounds;
+
+use crate::{api::now_playing::TrackInfo, StationMap};
+
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    /// segundos Unix
+    pub started_at: i64,
+    pub track: TrackInfo,
+    pub listeners: usize,
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    pub station: String,
+    pub from: i64,
+    pub to: i64,
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// `?day=AAAA-MM-DD` (UTC) ou `?from=&to=` em segundos Unix; sem nada, o dia de hoje
+#[get("/station/<name>/history?<day>&<from>&<to>")]
+pub fn history(
+    name: &str,
+    day: Option<&str>,
+    from: Option<i64>,
+    to: Option<i64>,
+    state: &State<StationMap>,
+) -> Result<Json<HistoryResponse>, Custom<String>> {
+    let cytoplasm = state
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+
+    let (from, to) = time_range(day, from, to)?;
+
+    let snapshots = cytoplasm
+        .history
+        .range(from, to)
+        .map_err(|e| Custom(Status::InternalServerError, e))?;
+    let likes = cytoplasm.likes.lock().unwrap();
+    let entries = snapshots
+        .iter()
+        .map(|snapshot| HistoryEntry {
+            started_at: snapshot.created_at,
+            track: TrackInfo::new(&snapshot.current_track, &likes),
+            listeners: snapshot.listeners,
+        })
+        .collect();
+
+    Ok(Json(HistoryResponse {
+        station: name.to_owned(),
+        from,
+        to,
+        entries,
+    }))
+}
+
+/// O intervalo `[from, to)` dos filtros `?day=AAAA-MM-DD` (UTC) ou `?from=&to=` em segundos Unix; sem nenhum
+/// dos dois, o dia de hoje. Filtro inválido é 400
+pub fn time_range(day: Option<&str>, from: Option<i64>, to: Option<i64>) -> Result<(i64, i64), Custom<String>> {
+    match (day, from, to) {
+        (Some(day), None, None) => parse_day(day).map(day_bounds).ok_or_else(|| {
+            Custom(Status::BadRequest, format!("dia inválido: {} (use AAAA-MM-DD)", day))
+        }),
+        (None, None, None) => Ok(day_bounds(OffsetDateTime::now_utc().date())),
+        (None, from, to) => Ok((from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX))),
+        (Some(_), _, _) => Err(Custom(Status::BadRequest, "use day ou from/to, não os dois".to_owned())),
+    }
+}
+
+/// `AAAA-MM-DD`
+pub fn parse_day(day: &str) -> Option<Date> {
+    let mut parts = day.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+
+    Date::from_calendar_date(year, month, day).ok()
+}
@@ -0,0 +1,36 @@
+// faixas de uma estação, na ordem da configuração
+
+use rocket::{response::status::NotFound, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{api::now_playing::TrackInfo, StationMap};
+
+#[derive(Serialize)]
+pub struct LibraryResponse {
+    pub station: String,
+    pub likes: usize,
+    pub tracks: Vec<TrackInfo>,
+}
+
+#[get("/station/<name>/tracks")]
+pub fn library(name: &str, state: &State<StationMap>) -> Result<Json<LibraryResponse>, NotFound<String>> {
+    let cytoplasm = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+
+    let likes = cytoplasm.likes.lock().unwrap();
+    let tracks = cytoplasm
+        .station
+        .lock()
+        .unwrap()
+        .tracks
+        .iter()
+        .map(|track| TrackInfo::new(track, &likes))
+        .collect();
+
+    Ok(Json(LibraryResponse {
+        station: name.to_owned(),
+        likes: likes.station_likes(),
+        tracks,
+    }))
+}
@@ -0,0 +1,128 @@
+// likes na faixa que está no ar e na estação, um por ouvinte
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{http::Status, response::status::Custom, serde::json::Json, State};
+use serde::Serialize;
+use tracing::info;
+
+use crate::{api::listener::ListenerId, output_stream::client::ClientIdentity, StationMap};
+
+/// likes novos que um endereço pode dar no mesmo alvo por `LIKE_WINDOW`: o bastante para alguns ouvintes atrás do
+/// mesmo NAT, mas não para um loop de requests sem cookie, que ganham um ouvinte novo a cada vez
+const MAX_LIKES_PER_ADDRESS: usize = 5;
+const LIKE_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// estação e, para likes em faixas, o `Track.source`
+type LikeTarget = (String, Option<String>);
+
+/// Likes recentes por endereço e alvo; fica só em memória
+#[derive(Default)]
+pub struct LikeThrottle {
+    recent: Mutex<HashMap<(IpAddr, LikeTarget), VecDeque<Instant>>>,
+}
+
+impl LikeThrottle {
+    /// Dá o like com `like` se o endereço ainda não estourou o limite nesse alvo; só likes novos contam no limite.
+    /// Sem endereço conhecido não há limite
+    fn like(
+        &self,
+        address: Option<IpAddr>,
+        target: LikeTarget,
+        like: impl FnOnce() -> Result<bool, String>,
+    ) -> Result<bool, Custom<String>> {
+        let Some(address) = address else {
+            return like().map_err(|e| Custom(Status::InternalServerError, e));
+        };
+
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|_, likes| {
+            likes.retain(|liked_at| now.duration_since(*liked_at) < LIKE_WINDOW);
+            !likes.is_empty()
+        });
+
+        let key = (address, target);
+        if recent.get(&key).map_or(0, VecDeque::len) >= MAX_LIKES_PER_ADDRESS {
+            info!(%address, station = %key.1.0, track = ?key.1.1, "like recusado: limite do endereço");
+            return Err(Custom(
+                Status::TooManyRequests,
+                "likes demais deste endereço, tente mais tarde".to_owned(),
+            ));
+        }
+
+        let added = like().map_err(|e| Custom(Status::InternalServerError, e))?;
+        if added {
+            recent.entry(key).or_default().push_back(now);
+        }
+        Ok(added)
+    }
+}
+
+#[derive(Serialize)]
+pub struct LikeResponse {
+    /// `false` se o ouvinte já tinha curtido
+    pub liked: bool,
+    pub likes: usize,
+}
+
+#[post("/station/<name>/like")]
+pub fn like_station(
+    name: &str,
+    listener: ListenerId,
+    client: ClientIdentity,
+    throttle: &State<LikeThrottle>,
+    state: &State<StationMap>,
+) -> Result<Json<LikeResponse>, Custom<String>> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+
+    let mut likes = station.likes.lock().unwrap();
+    let liked = throttle.like(client.remote_address, (name.to_owned(), None), || {
+        likes.like_station(&listener.0)
+    })?;
+
+    Ok(Json(LikeResponse {
+        liked,
+        likes: likes.station_likes(),
+    }))
+}
+
+/// Curte a faixa no ar no momento do request
+#[post("/station/<name>/now/like")]
+pub fn like_now_playing(
+    name: &str,
+    listener: ListenerId,
+    client: ClientIdentity,
+    throttle: &State<LikeThrottle>,
+    state: &State<StationMap>,
+) -> Result<Json<LikeResponse>, Custom<String>> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+    let source = station
+        .now_playing
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|playing| playing.track.source.clone())
+        .ok_or_else(|| Custom(Status::Conflict, format!("a estação {} ainda não está no ar", name)))?;
+
+    let mut likes = station.likes.lock().unwrap();
+    let liked = throttle.like(
+        client.remote_address,
+        (name.to_owned(), Some(source.clone())),
+        || likes.like_track(&source, &listener.0),
+    )?;
+
+    Ok(Json(LikeResponse {
+        liked,
+        likes: likes.track_likes(&source),
+    }))
+}
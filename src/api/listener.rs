@@ -0,0 +1,32 @@
+// identidade anônima do ouvinte, guardada num cookie, para deduplicar likes
+
+use rocket::{
+    http::Cookie,
+    request::{FromRequest, Outcome, Request},
+};
+use uuid::Uuid;
+
+const LISTENER_COOKIE: &str = "listener_id";
+
+/// Id do ouvinte que fez o request; na primeira visita um id novo é gerado e devolvido no cookie.
+/// Qualquer um pode mandar um cookie novo, então o id sozinho não segura likes repetidos: veja `LikeThrottle`
+pub struct ListenerId(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ListenerId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let cookies = request.cookies();
+
+        if let Some(cookie) = cookies.get(LISTENER_COOKIE) {
+            if Uuid::parse_str(cookie.value()).is_ok() {
+                return Outcome::Success(ListenerId(cookie.value().to_owned()));
+            }
+        }
+
+        let id = Uuid::new_v4().to_string();
+        cookies.add(Cookie::build((LISTENER_COOKIE, id.clone())).path("/").permanent());
+        Outcome::Success(ListenerId(id))
+    }
+}
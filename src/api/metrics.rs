@@ -0,0 +1,221 @@
+// métricas no formato texto do Prometheus, por estação e por codec
+
+use std::{fmt::Write, sync::atomic::Ordering, time::SystemTime};
+
+use rocket::{http::ContentType, State};
+use web_radio::objects::radio::Radio;
+
+use crate::{
+    output_encoder::audio_encoder::EncodedOutput,
+    output_stream::flood::FloodGuard,
+    StationMap,
+};
+
+/// Monta a exposição uma família de métricas por vez, como o formato exige
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl ToString) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        if labels.is_empty() {
+            let _ = writeln!(self.text, "{} {}", name, value.to_string());
+        } else {
+            let _ = writeln!(self.text, "{}{{{}}} {}", name, labels.join(","), value.to_string());
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[get("/metrics")]
+pub fn metrics(
+    radio: &State<Radio>,
+    state: &State<StationMap>,
+    flood: &State<FloodGuard>,
+) -> (ContentType, String) {
+    let mut out = Exposition { text: String::new() };
+    // estações em ordem estável, com os mounts na ordem de preferência de cada uma
+    let stations: Vec<_> = radio
+        .station_names()
+        .into_iter()
+        .filter_map(|name| state.get(&name).map(|cytoplasm| (name, cytoplasm)))
+        .collect();
+    let mounts: Vec<_> = stations
+        .iter()
+        .flat_map(|(name, cytoplasm)| {
+            cytoplasm
+                .mounts()
+                .into_iter()
+                .map(move |stream| (name.as_str(), stream))
+        })
+        .collect();
+    // saídas segmentadas (HLS, DASH), rotuladas com o nome da saída no lugar do codec
+    let segmented_outputs: Vec<(&str, &str, &dyn EncodedOutput)> = stations
+        .iter()
+        .flat_map(|(name, cytoplasm)| {
+            let hls = cytoplasm.hls.as_deref().map(|hls| ("hls", hls as &dyn EncodedOutput));
+            let dash = cytoplasm.dash.as_deref().map(|dash| ("dash", dash as &dyn EncodedOutput));
+            hls.into_iter()
+                .chain(dash)
+                .map(move |(output, encoded)| (name.as_str(), output, encoded))
+        })
+        .collect();
+
+    out.family("webradio_connections", "gauge", "Streams abertos em todas as estações.");
+    out.sample("webradio_connections", &[], radio.connection_count());
+
+    let rejections = flood.rejections();
+    out.family(
+        "webradio_flood_rejections_total",
+        "counter",
+        "Conexões recusadas pela proteção contra flood, por motivo.",
+    );
+    for (reason, count) in [
+        ("banned", rejections.banned),
+        ("too_many_connections", rejections.too_many_connections),
+        ("too_many_connects", rejections.too_many_connects),
+    ] {
+        out.sample("webradio_flood_rejections_total", &[("reason", reason)], count);
+    }
+
+    out.family("webradio_listeners", "gauge", "Ouvintes conectados, por estação e codec.");
+    for (station, stream) in &mounts {
+        let labels = [("station", *station), ("codec", stream.name())];
+        out.sample("webradio_listeners", &labels, stream.client_count());
+    }
+
+    out.family(
+        "webradio_bytes_sent_total",
+        "counter",
+        "Bytes de áudio enviados aos ouvintes, por estação e codec.",
+    );
+    for (station, stream) in &mounts {
+        let labels = [("station", *station), ("codec", stream.name())];
+        out.sample("webradio_bytes_sent_total", &labels, stream.counters().bytes_sent.get());
+    }
+    for (station, output, encoded) in &segmented_outputs {
+        let labels = [("station", *station), ("codec", *output)];
+        out.sample("webradio_bytes_sent_total", &labels, encoded.counters().bytes_sent.get());
+    }
+
+    out.family(
+        "webradio_encoded_bytes_total",
+        "counter",
+        "Bytes que saíram do ffmpeg, por estação e codec; a taxa é o bitrate real, inclusive nos codecs VBR.",
+    );
+    for (station, stream) in &mounts {
+        let labels = [("station", *station), ("codec", stream.name())];
+        out.sample("webradio_encoded_bytes_total", &labels, stream.counters().encoded.total());
+    }
+    for (station, output, encoded) in &segmented_outputs {
+        let labels = [("station", *station), ("codec", *output)];
+        out.sample("webradio_encoded_bytes_total", &labels, encoded.counters().encoded.total());
+    }
+
+    out.family(
+        "webradio_lagged_messages_total",
+        "counter",
+        "Mensagens de áudio puladas por ouvintes que ficaram para trás, por estação e codec.",
+    );
+    for (station, stream) in &mounts {
+        let labels = [("station", *station), ("codec", stream.name())];
+        let lagged = stream.counters().lagged_messages.load(Ordering::Relaxed);
+        out.sample("webradio_lagged_messages_total", &labels, lagged);
+    }
+
+    out.family(
+        "webradio_encoder_restarts_total",
+        "counter",
+        "Vezes que o ffmpeg foi reiniciado, por estação e codec.",
+    );
+    for (station, stream) in &mounts {
+        let labels = [("station", *station), ("codec", stream.name())];
+        let restarts = stream.counters().encoder_restarts.load(Ordering::Relaxed);
+        out.sample("webradio_encoder_restarts_total", &labels, restarts);
+    }
+    for (station, output, encoded) in &segmented_outputs {
+        let labels = [("station", *station), ("codec", *output)];
+        let restarts = encoded.counters().encoder_restarts.load(Ordering::Relaxed);
+        out.sample("webradio_encoder_restarts_total", &labels, restarts);
+    }
+
+    out.family(
+        "webradio_underruns_total",
+        "counter",
+        "Vezes que o buffer esvaziou e a estação voltou a bufferizar.",
+    );
+    for (name, cytoplasm) in &stations {
+        let underruns = cytoplasm.counters.underruns.load(Ordering::Relaxed);
+        out.sample("webradio_underruns_total", &[("station", name)], underruns);
+    }
+
+    out.family(
+        "webradio_time_underruns_total",
+        "counter",
+        "Iterações da thread dos encoders que terminaram atrasadas em relação ao áudio.",
+    );
+    for (name, cytoplasm) in &stations {
+        let underruns = cytoplasm.counters.time_underruns.load(Ordering::Relaxed);
+        out.sample("webradio_time_underruns_total", &[("station", name)], underruns);
+    }
+
+    out.family(
+        "webradio_buffer_packets",
+        "gauge",
+        "Pacotes PCM (~1s cada) no buffer entre o decoder e os encoders.",
+    );
+    for (name, cytoplasm) in &stations {
+        out.sample("webradio_buffer_packets", &[("station", name)], cytoplasm.buffer_depth());
+    }
+
+    out.family(
+        "webradio_track_info",
+        "gauge",
+        "Faixa no ar; sempre 1, com a faixa nos labels.",
+    );
+    for (name, cytoplasm) in &stations {
+        if let Some(playing) = cytoplasm.now_playing.read().unwrap().as_ref() {
+            let labels = [
+                ("station", name.as_str()),
+                ("title", &playing.track.title),
+                ("artist", &playing.track.artist),
+                ("album", &playing.track.album),
+            ];
+            out.sample("webradio_track_info", &labels, 1);
+        }
+    }
+
+    out.family(
+        "webradio_track_elapsed_seconds",
+        "gauge",
+        "Segundos desde o começo da faixa no ar.",
+    );
+    for (name, cytoplasm) in &stations {
+        if let Some(playing) = cytoplasm.now_playing.read().unwrap().as_ref() {
+            let elapsed = SystemTime::now()
+                .duration_since(playing.started_at)
+                .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+            out.sample("webradio_track_elapsed_seconds", &[("station", name)], elapsed);
+        }
+    }
+
+    (
+        ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]),
+        out.text,
+    )
+}
@@ -0,0 +1,13 @@
+// API JSON para os apps (web e mobile)
+
+pub mod admin;
+pub mod charts;
+pub mod history;
+pub mod library;
+pub mod likes;
+pub mod listener;
+pub mod metrics;
+pub mod now_playing;
+pub mod ranking;
+pub mod sessions;
+pub mod stations;
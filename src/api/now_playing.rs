@@ -0,0 +1,123 @@
+// o que está no ar em cada estação: consulta pontual e push por Server-Sent Events
+
+use std::time::SystemTime;
+
+use rocket::{
+    response::{
+        status::NotFound,
+        stream::{Event, EventStream},
+    },
+    serde::json::Json,
+    tokio::{select, sync::broadcast::error::RecvError},
+    Shutdown, State,
+};
+use serde::Serialize;
+use web_radio::objects::{station::likes::LikeBook, track::track::Track};
+
+use crate::{cytoplasm::cytoplasm::Cytoplasm, cytoplasm::playout::NowPlaying, StationMap};
+
+/// Faixa como os apps a veem; o caminho do arquivo fica de fora
+#[derive(Serialize)]
+pub struct TrackInfo {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_seconds: u32,
+    pub likes: usize,
+}
+
+impl TrackInfo {
+    pub fn new(track: &Track, likes: &LikeBook) -> TrackInfo {
+        TrackInfo {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            duration_seconds: track.duration,
+            likes: likes.track_likes(&track.source),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NowPlayingResponse {
+    pub station: String,
+    /// `None` enquanto a estação ainda está enchendo o buffer
+    pub track: Option<TrackInfo>,
+    pub elapsed_seconds: f64,
+    pub duration_seconds: u32,
+    pub next: Option<TrackInfo>,
+    pub listeners: usize,
+    pub station_likes: usize,
+}
+
+impl NowPlayingResponse {
+    fn new(name: &str, playing: Option<&NowPlaying>, station: &Cytoplasm) -> NowPlayingResponse {
+        // started_at pode estar um pouco no futuro (o buffer é empurrado de uma vez), então o mínimo é zero
+        let elapsed_seconds = playing
+            .and_then(|playing| SystemTime::now().duration_since(playing.started_at).ok())
+            .map(|elapsed| elapsed.as_secs_f64())
+            .unwrap_or(0.0);
+
+        let likes = station.likes.lock().unwrap();
+        NowPlayingResponse {
+            station: name.to_owned(),
+            track: playing.map(|playing| TrackInfo::new(&playing.track, &likes)),
+            elapsed_seconds,
+            duration_seconds: playing.map_or(0, |playing| playing.track.duration),
+            next: playing.map(|playing| TrackInfo::new(&playing.next, &likes)),
+            listeners: station.listener_count(),
+            station_likes: likes.station_likes(),
+        }
+    }
+
+    pub fn current(name: &str, station: &Cytoplasm) -> NowPlayingResponse {
+        let playing = station.now_playing.read().unwrap();
+        NowPlayingResponse::new(name, playing.as_ref(), station)
+    }
+}
+
+fn find_station<'a>(state: &'a StationMap, name: &str) -> Result<&'a Cytoplasm, NotFound<String>> {
+    state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))
+}
+
+#[get("/station/<name>/now")]
+pub fn now_playing(
+    name: &str,
+    state: &State<StationMap>,
+) -> Result<Json<NowPlayingResponse>, NotFound<String>> {
+    let station = find_station(state, name)?;
+
+    Ok(Json(NowPlayingResponse::current(name, station)))
+}
+
+/// Manda o estado atual assim que o cliente conecta e depois um evento `track` a cada troca de faixa
+#[get("/station/<name>/now/events")]
+pub fn now_playing_events<'a>(
+    name: &'a str,
+    state: &'a State<StationMap>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![Event + 'a], NotFound<String>> {
+    let station = find_station(state, name)?;
+    let mut track_events = station.track_events.subscribe();
+
+    Ok(EventStream! {
+        yield Event::json(&NowPlayingResponse::current(name, station)).event("track");
+
+        loop {
+            let playing = select! {
+                event = track_events.recv() => match event {
+                    Ok(playing) => playing,
+                    // perdemos eventos no meio do caminho; o próximo já traz o estado completo
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            let response = NowPlayingResponse::new(name, Some(&playing), station);
+            yield Event::json(&response).event("track");
+        }
+    })
+}
@@ -0,0 +1,74 @@
+// faixas com mais audiência de uma estação
+
+use rocket::{http::Status, response::status::Custom, serde::json::Json, State};
+use serde::Serialize;
+use web_radio::objects::track::audience::RankBy;
+
+use crate::{api::now_playing::TrackInfo, StationMap};
+
+const DEFAULT_RANKING_LIMIT: usize = 20;
+
+#[derive(Serialize)]
+pub struct RankedTrack {
+    pub position: usize,
+    pub track: TrackInfo,
+    pub plays: u64,
+    pub peak_listeners: usize,
+    pub last_peak_listeners: usize,
+    pub average_listeners: f64,
+}
+
+#[derive(Serialize)]
+pub struct RankingResponse {
+    pub station: String,
+    pub by: &'static str,
+    pub tracks: Vec<RankedTrack>,
+}
+
+/// `by` é `peak` (padrão) ou `average`
+#[get("/station/<name>/ranking?<by>&<limit>")]
+pub fn ranking(
+    name: &str,
+    by: Option<&str>,
+    limit: Option<usize>,
+    state: &State<StationMap>,
+) -> Result<Json<RankingResponse>, Custom<String>> {
+    let cytoplasm = state
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+    let (by, by_name) = match by.unwrap_or("peak") {
+        "peak" => (RankBy::Peak, "peak"),
+        "average" => (RankBy::Average, "average"),
+        other => return Err(Custom(Status::BadRequest, format!("ranking desconhecido: {}", other))),
+    };
+
+    let audience = cytoplasm.audience.lock().unwrap();
+    let likes = cytoplasm.likes.lock().unwrap();
+    let station = cytoplasm.station.lock().unwrap();
+
+    // faixas que saíram da estação continuam no histórico de audiência, mas não no ranking
+    let tracks = audience
+        .ranking(by)
+        .into_iter()
+        .filter_map(|(source, audience)| {
+            let track = station.tracks.iter().find(|track| track.source == source)?;
+            Some((track, audience))
+        })
+        .take(limit.unwrap_or(DEFAULT_RANKING_LIMIT))
+        .enumerate()
+        .map(|(index, (track, audience))| RankedTrack {
+            position: index + 1,
+            track: TrackInfo::new(track, &likes),
+            plays: audience.plays,
+            peak_listeners: audience.peak_listeners,
+            last_peak_listeners: audience.last_peak_listeners,
+            average_listeners: audience.average_listeners(),
+        })
+        .collect();
+
+    Ok(Json(RankingResponse {
+        station: name.to_owned(),
+        by: by_name,
+        tracks,
+    }))
+}
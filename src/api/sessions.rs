@@ -0,0 +1,135 @@
+// sessões dos ouvintes para o admin: a lista crua e o relatório de horas ouvidas por dia, em JSON ou CSV
+
+use rocket::{http::Status, response::status::Custom, serde::json::Json, State};
+use serde::Serialize;
+use web_radio::objects::station::sessions::{daily_report, report_csv, sessions_csv, DailyListening, SessionRecord};
+
+use crate::{
+    api::{admin::AdminAuth, history::time_range},
+    StationMap,
+};
+
+/// JSON por padrão; `?format=csv` baixa uma planilha
+#[derive(Responder)]
+pub enum Export<T> {
+    Json(Json<T>),
+    #[response(content_type = "text/csv")]
+    Csv(String),
+}
+
+#[derive(Serialize)]
+pub struct SessionsResponse {
+    pub from: i64,
+    pub to: i64,
+    pub sessions: Vec<SessionRecord>,
+}
+
+#[derive(Serialize)]
+pub struct ReportResponse {
+    /// `None` quando o relatório junta todas as estações
+    pub station: Option<String>,
+    pub from: i64,
+    pub to: i64,
+    pub sessions: usize,
+    pub listening_hours: f64,
+    pub average_session_seconds: f64,
+    pub days: Vec<DailyListening>,
+}
+
+/// Sessões encerradas, da estação pedida ou de todas, que começaram no intervalo.
+/// `?day=AAAA-MM-DD` (UTC) ou `?from=&to=` em segundos Unix; sem nada, o dia de hoje, como no histórico
+#[get("/admin/sessions?<station>&<day>&<from>&<to>&<format>")]
+pub fn sessions(
+    _admin: AdminAuth,
+    station: Option<&str>,
+    day: Option<&str>,
+    from: Option<i64>,
+    to: Option<i64>,
+    format: Option<&str>,
+    state: &State<StationMap>,
+) -> Result<Export<SessionsResponse>, Custom<String>> {
+    let (from, to) = time_range(day, from, to)?;
+    let csv = wants_csv(format)?;
+    let sessions = collect(state, station, from, to)?;
+
+    if csv {
+        return Ok(Export::Csv(sessions_csv(&sessions)));
+    }
+    Ok(Export::Json(Json(SessionsResponse { from, to, sessions })))
+}
+
+/// Horas ouvidas, duração média das sessões e ouvintes únicos por dia; os mesmos filtros de `/admin/sessions`
+#[get("/admin/sessions/report?<station>&<day>&<from>&<to>&<format>")]
+pub fn report(
+    _admin: AdminAuth,
+    station: Option<&str>,
+    day: Option<&str>,
+    from: Option<i64>,
+    to: Option<i64>,
+    format: Option<&str>,
+    state: &State<StationMap>,
+) -> Result<Export<ReportResponse>, Custom<String>> {
+    let (from, to) = time_range(day, from, to)?;
+    let csv = wants_csv(format)?;
+    let sessions = collect(state, station, from, to)?;
+    let days = daily_report(&sessions);
+
+    if csv {
+        return Ok(Export::Csv(report_csv(&days)));
+    }
+    let seconds: i64 = sessions.iter().map(SessionRecord::seconds).sum();
+    Ok(Export::Json(Json(ReportResponse {
+        station: station.map(str::to_owned),
+        from,
+        to,
+        sessions: sessions.len(),
+        listening_hours: seconds as f64 / 3600.0,
+        average_session_seconds: if sessions.is_empty() {
+            0.0
+        } else {
+            seconds as f64 / sessions.len() as f64
+        },
+        days,
+    })))
+}
+
+fn wants_csv(format: Option<&str>) -> Result<bool, Custom<String>> {
+    match format.unwrap_or("json") {
+        "json" => Ok(false),
+        "csv" => Ok(true),
+        other => Err(Custom(
+            Status::BadRequest,
+            format!("formato desconhecido: {} (use json ou csv)", other),
+        )),
+    }
+}
+
+/// As sessões de uma estação, ou de todas, da mais antiga para a mais nova
+fn collect(
+    state: &StationMap,
+    station: Option<&str>,
+    from: i64,
+    to: i64,
+) -> Result<Vec<SessionRecord>, Custom<String>> {
+    let mut sessions = Vec::new();
+    match station {
+        Some(name) => {
+            let cytoplasm = state
+                .get(name)
+                .ok_or_else(|| Custom(Status::NotFound, format!("estação desconhecida: {}", name)))?;
+            sessions.extend(cytoplasm.sessions.range(from, to).map_err(internal_error)?);
+        }
+        None => {
+            for cytoplasm in state.values() {
+                sessions.extend(cytoplasm.sessions.range(from, to).map_err(internal_error)?);
+            }
+        }
+    }
+    sessions.sort_by_key(|session| (session.started_at, session.ended_at));
+
+    Ok(sessions)
+}
+
+fn internal_error(e: String) -> Custom<String> {
+    Custom(Status::InternalServerError, e)
+}
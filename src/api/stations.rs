@@ -0,0 +1,92 @@
+// diretório das estações, com as estatísticas ao vivo de cada uma
+
+use rocket::{serde::json::Json, State};
+use serde::Serialize;
+use web_radio::objects::radio::Radio;
+
+use crate::{api::now_playing::NowPlayingResponse, StationMap};
+
+#[derive(Serialize)]
+pub struct MountInfo {
+    pub codec: &'static str,
+    pub mount: String,
+    /// caminho para tocar o mount, relativo à raiz do servidor
+    pub url: String,
+    pub listeners: usize,
+}
+
+/// Saída HLS ou DASH; sem contagem de ouvintes, já que os players só baixam segmentos
+#[derive(Serialize)]
+pub struct SegmentedInfo {
+    pub codec: &'static str,
+    /// caminho da playlist ou do manifesto
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct StationInfo {
+    pub name: String,
+    pub frequency: f32,
+    pub state: &'static str,
+    pub default_codec: &'static str,
+    /// na ordem de preferência da estação
+    pub mounts: Vec<MountInfo>,
+    pub hls: Option<SegmentedInfo>,
+    pub dash: Option<SegmentedInfo>,
+    pub listeners: usize,
+    pub now_playing: NowPlayingResponse,
+}
+
+#[derive(Serialize)]
+pub struct StationDirectory {
+    /// conexões abertas em todas as estações
+    pub connections: usize,
+    pub stations: Vec<StationInfo>,
+}
+
+#[get("/stations")]
+pub fn stations(radio: &State<Radio>, state: &State<StationMap>) -> Json<StationDirectory> {
+    let mut stations = Vec::new();
+
+    for name in radio.station_names() {
+        // estação que o Radio conhece mas que não está tocando não tem o que mostrar
+        let Some(cytoplasm) = state.get(&name) else {
+            continue;
+        };
+
+        let mounts: Vec<MountInfo> = cytoplasm
+            .mounts()
+            .into_iter()
+            .map(|stream| MountInfo {
+                codec: stream.name(),
+                mount: stream.mount().to_owned(),
+                url: format!("/station/{}", stream.mount()),
+                listeners: stream.list_clients().len(),
+            })
+            .collect();
+
+        let station = radio.stations[&name].lock().unwrap();
+        stations.push(StationInfo {
+            name: station.name.clone(),
+            frequency: station.frequency,
+            state: station.state_name(),
+            default_codec: cytoplasm.default_codec.name(),
+            listeners: mounts.iter().map(|mount| mount.listeners).sum(),
+            mounts,
+            hls: cytoplasm.hls.as_ref().map(|hls| SegmentedInfo {
+                codec: hls.codec().name(),
+                url: format!("/station/{}/hls/live.m3u8", name),
+            }),
+            dash: cytoplasm.dash.as_ref().map(|dash| SegmentedInfo {
+                codec: dash.codec().name(),
+                url: format!("/station/{}/dash/live.mpd", name),
+            }),
+            now_playing: NowPlayingResponse::current(&name, cytoplasm),
+        });
+    }
+
+    Json(StationDirectory {
+        connections: radio.connection_count(),
+        stations,
+    })
+}
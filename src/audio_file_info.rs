@@ -1,7 +1,23 @@
-use std::{fs::File, path::PathBuf, process::Command};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use web_radio::objects::json_store::{load_json_or_default, save_json_atomically};
+
+/// Nome do arquivo de cache de probes, criado dentro do diretório de cada estação
+pub const PROBE_CACHE_FILE_NAME: &str = ".probe_cache.json";
+
+/// Extensões que consideramos como áudio ao varrer a biblioteca de uma estação
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "ogg", "opus", "flac", "m4a", "aac"];
 
 /// Representa as informações de um arquivo de áudio
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct AudioFileInfo {
     /// Localização do arquivo de áudio
     location: PathBuf,
@@ -12,6 +28,20 @@ pub struct AudioFileInfo {
     // TODO: talvez mais campos legais de extrair do arquivo de áudio? bitrate, contagem de canais, título da música (se houver), outras..?
 }
 
+impl AudioFileInfo {
+    pub fn location(&self) -> &Path {
+        &self.location
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    pub fn audio_milliseconds(&self) -> u64 {
+        self.audio_milliseconds
+    }
+}
+
 // Extrair as informações de um arquivo de áudio
 pub fn query(location: PathBuf) -> Result<AudioFileInfo, String> {
     let metadata = File::open(&location)
@@ -52,3 +82,153 @@ pub fn query(location: PathBuf) -> Result<AudioFileInfo, String> {
         audio_milliseconds: (audio_seconds_float * 1000.0) as u64,
     })
 }
+
+/// Uma entrada do cache: o resultado do probe, junto com o tamanho e o mtime do arquivo no momento do probe.
+/// Se qualquer um dos dois mudar, a entrada é considerada velha e o arquivo é inspecionado novamente.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ProbeCacheEntry {
+    size_bytes: u64,
+    modified: Duration, // desde a UNIX epoch
+    info: AudioFileInfo,
+}
+
+/// Cache persistente dos resultados do `query`, indexado pelo caminho do arquivo.
+///
+/// Spawnar um ffprobe por arquivo toda vez que o servidor sobe fica caro em bibliotecas grandes;
+/// com o cache, só os arquivos novos ou modificados desde a última varredura são inspecionados.
+pub struct ProbeCache {
+    cache_path: PathBuf,
+    entries: HashMap<PathBuf, ProbeCacheEntry>,
+    dirty: bool,
+}
+
+impl ProbeCache {
+    /// Carrega o cache do disco. Se o arquivo não existir ou estiver corrompido, começa com um cache vazio
+    pub fn load(cache_path: PathBuf) -> ProbeCache {
+        let entries = load_json_or_default(&cache_path).unwrap_or_else(|e| {
+            warn!(
+                cache = %cache_path.display(),
+                "cache de probes ilegível, descartando: {}",
+                e
+            );
+            HashMap::new()
+        });
+
+        ProbeCache {
+            cache_path,
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// Igual ao `query`, mas reaproveita o resultado do cache se o arquivo não mudou desde o último probe
+    pub fn query(&mut self, location: PathBuf) -> Result<AudioFileInfo, String> {
+        self.query_with(location, query)
+    }
+
+    /// O `query` do cache com outro probe para os arquivos novos ou modificados
+    pub fn query_with(
+        &mut self,
+        location: PathBuf,
+        probe: impl FnOnce(PathBuf) -> Result<AudioFileInfo, String>,
+    ) -> Result<AudioFileInfo, String> {
+        let (size_bytes, modified) = file_fingerprint(&location)?;
+
+        if let Some(entry) = self.entries.get(&location) {
+            if entry.size_bytes == size_bytes && entry.modified == modified {
+                return Ok(entry.info.clone());
+            }
+        }
+
+        let info = probe(location.clone())?;
+        self.entries.insert(
+            location,
+            ProbeCacheEntry {
+                size_bytes,
+                modified,
+                info: info.clone(),
+            },
+        );
+        self.dirty = true;
+
+        Ok(info)
+    }
+
+    /// Remove as entradas de arquivos que não estão em `seen` (ex.: arquivos apagados da biblioteca)
+    pub fn retain(&mut self, seen: &HashSet<PathBuf>) {
+        let before = self.entries.len();
+        self.entries.retain(|location, _| seen.contains(location));
+        if self.entries.len() != before {
+            self.dirty = true;
+        }
+    }
+
+    /// Persiste o cache em disco, se algo mudou
+    pub fn save(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        save_json_atomically(&self.cache_path, &self.entries).map_err(|e| format!("probe_cache: {}", e))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Tamanho e mtime do arquivo, usados para saber se uma entrada do cache ainda vale
+fn file_fingerprint(location: &Path) -> Result<(u64, Duration), String> {
+    let metadata = fs::metadata(location)
+        .map_err(|e| format!("probe_cache: falha ao obter metadados do arquivo: {}", e))?;
+    let modified = metadata
+        .modified()
+        .map_err(|e| format!("probe_cache: mtime indisponível: {}", e))?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Ok((metadata.len(), modified))
+}
+
+/// Varre os arquivos de áudio de um diretório, usando (e atualizando) o cache de probes que fica nele.
+/// Arquivos que falharem no probe são reportados e ficam de fora do resultado.
+pub fn scan_library(directory: &Path) -> Vec<AudioFileInfo> {
+    let mut cache = ProbeCache::load(directory.join(PROBE_CACHE_FILE_NAME));
+    let mut seen = HashSet::new();
+    let mut library = Vec::new();
+
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!(directory = %directory.display(), "falha ao listar o diretório: {}", e);
+            return library;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let location = entry.path();
+        if !location.is_file() || !is_audio_file(&location) {
+            continue;
+        }
+
+        seen.insert(location.clone());
+        match cache.query(location) {
+            Ok(info) => library.push(info),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    // entradas de arquivos que sumiram do diretório não servem mais pra nada
+    cache.retain(&seen);
+    if let Err(e) = cache.save() {
+        warn!("{}", e);
+    }
+
+    library
+}
+
+fn is_audio_file(location: &Path) -> bool {
+    match location.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
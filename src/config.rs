@@ -0,0 +1,1132 @@
+use std::{
+    collections::HashSet,
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use tracing::{info, level_filters::LevelFilter, warn};
+
+use web_radio::objects::{
+    station::{station::Station, station_state::Buffering},
+    track::playlist,
+};
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::{
+    cytoplasm::cytoplasm::{SETPOINT_HIGH, SETPOINT_LOW},
+    output_encoder::audio_encoder::{OutputCodec, MAX_STATION_LISTENERS},
+    output_stream::{bans::MAX_BAN_MINUTES, dash::DashCodec, hls::HlsCodec, websocket},
+};
+
+/// Caminho padrão do arquivo de configuração; pode ser trocado pela variável de ambiente `WEB_RADIO_CONFIG`
+pub const DEFAULT_CONFIG_PATH: &str = "./config.yaml";
+
+const DEFAULT_SEED: u64 = 42;
+const MIN_TOKEN_LENGTH: usize = 16;
+const MIN_BITRATE_KBPS: u32 = 8;
+const MAX_BITRATE_KBPS: u32 = 320;
+const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 8;
+const DEFAULT_MAX_CONNECTS_PER_MINUTE: usize = 30;
+const DEFAULT_FLOOD_BAN_MINUTES: u64 = 10;
+const DEFAULT_PROXY_HEADER: &str = "X-Real-IP";
+const DEFAULT_SEGMENT_SECONDS: u32 = 6;
+const DEFAULT_SEGMENT_WINDOW: usize = 6;
+const SEGMENT_SECONDS: std::ops::RangeInclusive<u32> = 2..=20;
+const SEGMENT_WINDOW: std::ops::RangeInclusive<usize> = 3..=30;
+
+/// Setpoints do buffer de pacotes PCM entre o decoder e os encoders, em pacotes (~1s cada)
+#[derive(Clone, Copy, Debug)]
+pub struct BufferConfig {
+    /// o decoder pausa quando o buffer chega aqui, e o encoder só começa a consumir com o buffer cheio
+    pub setpoint_high: usize,
+    /// o decoder volta a enfileirar quando o buffer desce até aqui
+    pub setpoint_low: usize,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        BufferConfig {
+            setpoint_high: SETPOINT_HIGH,
+            setpoint_low: SETPOINT_LOW,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CodecConfig {
+    pub codec: OutputCodec,
+    /// `None` nos codecs sem perdas (flac), que não têm bitrate
+    pub bitrate_kbps: Option<u32>,
+    /// limite só deste codec; por padrão, o `max_listeners` da estação
+    pub max_listeners: usize,
+}
+
+/// Saída segmentada (HLS ou DASH) de uma estação: segmentos de `segment_seconds` e um manifesto com os últimos `window`
+#[derive(Clone, Debug)]
+pub struct SegmentedConfig<C> {
+    pub codec: C,
+    pub bitrate_kbps: u32,
+    pub segment_seconds: u32,
+    pub window: usize,
+}
+
+pub type HlsConfig = SegmentedConfig<HlsCodec>;
+pub type DashConfig = SegmentedConfig<DashCodec>;
+
+/// Saída WebSocket de baixa latência (pacotes Opus) de uma estação
+#[derive(Clone, Debug)]
+pub struct WebSocketConfig {
+    pub bitrate_kbps: u32,
+    /// limite só deste mount; por padrão, o `max_listeners` da estação
+    pub max_listeners: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct StationConfig {
+    pub name: String,
+    pub directory: PathBuf,
+    /// playlist M3U/PLS/XSPF com as faixas; se ausente, usamos o metadata.json do diretório
+    pub playlist: Option<PathBuf>,
+    pub seed: u64,
+    pub frequency: f32,
+    pub max_listeners: usize,
+    /// vagas de `max_listeners` que só usuários autenticados podem ocupar
+    pub reserved_slots: usize,
+    /// mount (`estação/codec`) para onde mandamos os ouvintes quando esta estação está cheia; sem ele, respondemos 503
+    pub fallback_mount: Option<String>,
+    /// só aceita ouvintes com URL assinada (`?token=`)
+    pub private: bool,
+    pub buffer: BufferConfig,
+    pub codecs: Vec<CodecConfig>,
+    /// codec servido em `/station/<nome>` quando o cliente não indica preferência; por padrão, o primeiro da lista
+    pub default_codec: OutputCodec,
+    /// saída HLS em `/station/<nome>/hls/live.m3u8`, além dos streams HTTP
+    pub hls: Option<HlsConfig>,
+    /// saída MPEG-DASH em `/station/<nome>/dash/live.mpd`
+    pub dash: Option<DashConfig>,
+    /// saída WebSocket em `/station/<nome>/ws`
+    pub websocket: Option<WebSocketConfig>,
+}
+
+/// Rotas `/admin`. Sem token configurado, elas respondem 401 para todo mundo
+#[derive(Clone, Debug, Default)]
+pub struct AdminConfig {
+    /// enviado como `Authorization: Bearer <token>`; a variável de ambiente `WEB_RADIO_ADMIN_TOKEN` tem precedência
+    pub token: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// uma linha legível por evento
+    #[default]
+    Text,
+    /// um objeto JSON por linha, para quem coleta os logs
+    Json,
+}
+
+/// Níveis e formato dos logs. A variável de ambiente `WEB_RADIO_LOG` (sintaxe do `RUST_LOG`) substitui os níveis daqui
+#[derive(Clone, Debug)]
+pub struct LoggingConfig {
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    /// nível por módulo, ex.: `web_radio::output_stream: warn` cala os eventos de cada cliente
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: LevelFilter::INFO,
+            format: LogFormat::Text,
+            modules: Vec::new(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Os níveis no formato de diretivas do `EnvFilter`, ex.: `info,web_radio::output_stream=warn`
+    pub fn directives(&self) -> String {
+        let mut directives = vec![self.level.to_string()];
+        for (module, level) in &self.modules {
+            directives.push(format!("{}={}", module, level));
+        }
+        directives.join(",")
+    }
+}
+
+/// Segredo das URLs assinadas dos streams (`?token=`). Sem ele, não há estações privadas nem emissão de tokens
+#[derive(Clone, Debug, Default)]
+pub struct TokenConfig {
+    /// a variável de ambiente `WEB_RADIO_TOKEN_SECRET` tem precedência
+    pub secret: Option<String>,
+}
+
+/// Proteção contra um mesmo endereço abrindo conexões demais nos endpoints de stream
+#[derive(Clone, Copy, Debug)]
+pub struct FloodConfig {
+    /// streams abertos ao mesmo tempo por endereço, somando todas as estações
+    pub max_connections_per_ip: usize,
+    /// conexões novas por endereço numa janela de um minuto
+    pub max_connects_per_minute: usize,
+    /// por quanto tempo quem estoura o limite de conexões novas fica banido
+    pub ban_minutes: u64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        FloodConfig {
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            max_connects_per_minute: DEFAULT_MAX_CONNECTS_PER_MINUTE,
+            ban_minutes: DEFAULT_FLOOD_BAN_MINUTES,
+        }
+    }
+}
+
+/// Proxy reverso na frente do servidor. Sem `trusted`, o endereço de cada cliente é o da conexão e o header é ignorado
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    /// endereços dos proxies; só das conexões vindas deles o header é aceito
+    pub trusted: Vec<IpAddr>,
+    /// header com o endereço do cliente; numa lista (`X-Forwarded-For`), vale o último, o que o proxy acrescentou
+    pub header: String,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            trusted: Vec::new(),
+            header: DEFAULT_PROXY_HEADER.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RadioConfig {
+    pub seed: u64,
+    pub logging: LoggingConfig,
+    pub admin: AdminConfig,
+    pub tokens: TokenConfig,
+    pub flood: FloodConfig,
+    pub proxy: ProxyConfig,
+    pub stations: Vec<StationConfig>,
+}
+
+impl RadioConfig {
+    /// Lê e valida o arquivo de configuração. Caminhos relativos são resolvidos a partir do diretório do arquivo.
+    /// Em caso de erro, retorna todos os problemas encontrados, não só o primeiro.
+    pub fn load(path: &Path) -> Result<RadioConfig, Vec<String>> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| vec![format!("falha ao ler {}: {}", path.display(), e)])?;
+        let base_directory = path.parent().unwrap_or(Path::new("."));
+
+        Self::parse(&source, base_directory)
+    }
+
+    pub fn parse(source: &str, base_directory: &Path) -> Result<RadioConfig, Vec<String>> {
+        let documents = YamlLoader::load_from_str(source)
+            .map_err(|e| vec![format!("YAML inválido: {}", e)])?;
+        let root = documents.first().unwrap_or(&Yaml::Null);
+
+        let mut reader = ConfigReader {
+            errors: Vec::new(),
+            base_directory: base_directory.to_path_buf(),
+        };
+        let config = reader.radio(root);
+
+        if reader.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(reader.errors)
+        }
+    }
+}
+
+impl StationConfig {
+    /// Monta o `Station` com as faixas da playlist ou do metadata.json.
+    /// Faixas cujos arquivos não existem são reportadas e descartadas.
+    pub fn load_station(&self) -> Result<Station, String> {
+        let directory = self.directory.to_string_lossy().into_owned();
+
+        let tracks = match &self.playlist {
+            Some(playlist_path) => {
+                let import = playlist::import(playlist_path)?;
+                info!(
+                    station = %self.name,
+                    playlist = %playlist_path.display(),
+                    "playlist importada: {}",
+                    import
+                );
+                import.tracks
+            }
+            None => {
+                let tracks = Station::read_metadata(&self.directory)?;
+                let (found, missing): (Vec<_>, Vec<_>) = tracks
+                    .into_iter()
+                    .partition(|track| self.directory.join(&track.source).is_file());
+                for track in &missing {
+                    warn!(
+                        station = %self.name,
+                        file = %self.directory.join(&track.source).display(),
+                        "arquivo não encontrado"
+                    );
+                }
+                found
+            }
+        };
+
+        if tracks.is_empty() {
+            return Err(format!("config: estação {} não tem nenhuma faixa tocável", self.name));
+        }
+
+        Ok(Station::with_tracks(
+            self.name.clone(),
+            directory,
+            self.frequency,
+            Box::new(Buffering),
+            tracks,
+        ))
+    }
+}
+
+/// Percorre a árvore YAML acumulando erros, com o caminho de cada campo (ex.: `stations[0].codecs[1].bitrate`)
+struct ConfigReader {
+    errors: Vec<String>,
+    base_directory: PathBuf,
+}
+
+impl ConfigReader {
+    fn radio(&mut self, root: &Yaml) -> RadioConfig {
+        if !root.is_hash() {
+            self.errors
+                .push("a raiz da configuração deve ser um mapa".to_owned());
+            return RadioConfig {
+                seed: DEFAULT_SEED,
+                logging: LoggingConfig::default(),
+                admin: AdminConfig::default(),
+                tokens: TokenConfig::default(),
+                flood: FloodConfig::default(),
+                proxy: ProxyConfig::default(),
+                stations: Vec::new(),
+            };
+        }
+        self.check_keys(
+            root,
+            "",
+            &["seed", "logging", "admin", "tokens", "flood", "proxy", "stations"],
+        );
+
+        let seed = self.optional_u64(&root["seed"], "seed").unwrap_or(DEFAULT_SEED);
+        let logging = self.logging(&root["logging"], "logging");
+        let admin = self.admin(&root["admin"], "admin");
+        let tokens = self.tokens(&root["tokens"], "tokens");
+        let flood = self.flood(&root["flood"], "flood");
+        let proxy = self.proxy(&root["proxy"], "proxy");
+
+        let mut stations = Vec::new();
+        match root["stations"].as_vec() {
+            Some(nodes) if !nodes.is_empty() => {
+                for (index, node) in nodes.iter().enumerate() {
+                    if let Some(station) = self.station(node, &format!("stations[{}]", index), seed)
+                    {
+                        stations.push(station);
+                    }
+                }
+            }
+            _ => self
+                .errors
+                .push("stations: é preciso declarar pelo menos uma estação".to_owned()),
+        }
+
+        let mut names = HashSet::new();
+        for station in &stations {
+            if !names.insert(station.name.clone()) {
+                self.errors
+                    .push(format!("stations: estação {} declarada mais de uma vez", station.name));
+            }
+        }
+
+        // o fallback precisa apontar para um mount que existe, e de outra estação
+        for (index, station) in stations.iter().enumerate() {
+            let Some(fallback) = &station.fallback_mount else {
+                continue;
+            };
+            let target = fallback.split_once('/').and_then(|(name, codec)| {
+                let target = stations.iter().find(|s| s.name == name)?;
+                target
+                    .codecs
+                    .iter()
+                    .any(|c| c.codec.name() == codec)
+                    .then_some(target)
+            });
+            match target {
+                None => self.errors.push(format!(
+                    "stations[{}].fallback_mount: '{}' não é um mount conhecido (use estação/codec)",
+                    index, fallback
+                )),
+                Some(target) if target.name == station.name => self.errors.push(format!(
+                    "stations[{}].fallback_mount: deve ser de outra estação",
+                    index
+                )),
+                Some(_) => {}
+            }
+        }
+
+        // nem em ciclo: A -> B -> A, com as duas cheias, redirecionaria o ouvinte para sempre
+        let fallback_of = |name: &str| {
+            stations
+                .iter()
+                .find(|s| s.name == name)
+                .and_then(|s| s.fallback_mount.as_deref())
+                .and_then(|fallback| fallback.split_once('/'))
+                .map(|(target, _)| target.to_owned())
+        };
+        for (index, station) in stations.iter().enumerate() {
+            let mut chain = vec![station.name.clone()];
+            while let Some(next) = fallback_of(chain.last().unwrap()) {
+                // o fallback para a própria estação já foi apontado acima
+                if next == station.name && chain.len() > 1 {
+                    chain.push(next);
+                    self.errors.push(format!(
+                        "stations[{}].fallback_mount: ciclo de fallback ({})",
+                        index,
+                        chain.join(" -> ")
+                    ));
+                    break;
+                }
+                if chain.contains(&next) {
+                    break;
+                }
+                chain.push(next);
+            }
+        }
+
+        if tokens.secret.is_none() {
+            for (index, station) in stations.iter().enumerate().filter(|(_, s)| s.private) {
+                self.errors.push(format!(
+                    "stations[{}].private: estação {} é privada, mas tokens.secret não foi configurado",
+                    index, station.name
+                ));
+            }
+        }
+
+        RadioConfig {
+            seed,
+            logging,
+            admin,
+            tokens,
+            flood,
+            proxy,
+            stations,
+        }
+    }
+
+    fn logging(&mut self, node: &Yaml, path: &str) -> LoggingConfig {
+        let mut logging = LoggingConfig::default();
+        if node.is_badvalue() || node.is_null() {
+            return logging;
+        }
+        if !node.is_hash() {
+            self.errors.push(format!("{}: esperava um mapa", path));
+            return logging;
+        }
+        self.check_keys(node, path, &["level", "format", "modules"]);
+
+        if !node["level"].is_badvalue() && !node["level"].is_null() {
+            if let Some(level) = self.level(&node["level"], &format!("{}.level", path)) {
+                logging.level = level;
+            }
+        }
+
+        match &node["format"] {
+            Yaml::BadValue | Yaml::Null => {}
+            value => match self.required_str(value, &format!("{}.format", path)).as_deref() {
+                Some("text") => logging.format = LogFormat::Text,
+                Some("json") => logging.format = LogFormat::Json,
+                Some(other) => self.errors.push(format!(
+                    "{}.format: '{}' não é um formato (use text ou json)",
+                    path, other
+                )),
+                None => {}
+            },
+        }
+
+        match &node["modules"] {
+            Yaml::BadValue | Yaml::Null => {}
+            Yaml::Hash(modules) => {
+                for (module, level) in modules {
+                    let Some(module) = module.as_str() else {
+                        self.errors.push(format!(
+                            "{}.modules: esperava o nome de um módulo, encontrou {}",
+                            path,
+                            yaml_display(module)
+                        ));
+                        continue;
+                    };
+                    if let Some(level) = self.level(level, &format!("{}.modules.{}", path, module)) {
+                        logging.modules.push((module.to_owned(), level));
+                    }
+                }
+            }
+            other => self.errors.push(format!(
+                "{}.modules: esperava um mapa de módulo para nível, encontrou {}",
+                path,
+                yaml_display(other)
+            )),
+        }
+
+        logging
+    }
+
+    fn level(&mut self, node: &Yaml, path: &str) -> Option<LevelFilter> {
+        let name = self.required_str(node, path)?;
+        match LevelFilter::from_str(&name) {
+            Ok(level) => Some(level),
+            Err(_) => {
+                self.errors.push(format!(
+                    "{}: '{}' não é um nível (use trace, debug, info, warn, error ou off)",
+                    path, name
+                ));
+                None
+            }
+        }
+    }
+
+    fn admin(&mut self, node: &Yaml, path: &str) -> AdminConfig {
+        let mut admin = AdminConfig::default();
+        if !node.is_badvalue() && !node.is_null() {
+            if !node.is_hash() {
+                self.errors.push(format!("{}: esperava um mapa", path));
+                return admin;
+            }
+            self.check_keys(node, path, &["token"]);
+
+            if !node["token"].is_badvalue() && !node["token"].is_null() {
+                admin.token = self.required_str(&node["token"], &format!("{}.token", path));
+            }
+        }
+
+        if let Ok(token) = std::env::var("WEB_RADIO_ADMIN_TOKEN") {
+            admin.token = Some(token);
+        }
+        if admin.token.as_ref().is_some_and(|token| token.len() < MIN_TOKEN_LENGTH) {
+            self.errors.push(format!(
+                "{}.token: deve ter pelo menos {} caracteres",
+                path, MIN_TOKEN_LENGTH
+            ));
+        }
+
+        admin
+    }
+
+    fn tokens(&mut self, node: &Yaml, path: &str) -> TokenConfig {
+        let mut tokens = TokenConfig::default();
+        if !node.is_badvalue() && !node.is_null() {
+            if !node.is_hash() {
+                self.errors.push(format!("{}: esperava um mapa", path));
+                return tokens;
+            }
+            self.check_keys(node, path, &["secret"]);
+
+            if !node["secret"].is_badvalue() && !node["secret"].is_null() {
+                tokens.secret = self.required_str(&node["secret"], &format!("{}.secret", path));
+            }
+        }
+
+        if let Ok(secret) = std::env::var("WEB_RADIO_TOKEN_SECRET") {
+            tokens.secret = Some(secret);
+        }
+        if tokens.secret.as_ref().is_some_and(|secret| secret.len() < MIN_TOKEN_LENGTH) {
+            self.errors.push(format!(
+                "{}.secret: deve ter pelo menos {} caracteres",
+                path, MIN_TOKEN_LENGTH
+            ));
+        }
+
+        tokens
+    }
+
+    fn flood(&mut self, node: &Yaml, path: &str) -> FloodConfig {
+        let mut flood = FloodConfig::default();
+        if node.is_badvalue() || node.is_null() {
+            return flood;
+        }
+        if !node.is_hash() {
+            self.errors.push(format!("{}: esperava um mapa", path));
+            return flood;
+        }
+        self.check_keys(
+            node,
+            path,
+            &["max_connections_per_ip", "max_connects_per_minute", "ban_minutes"],
+        );
+
+        for (key, value) in [
+            ("max_connections_per_ip", &mut flood.max_connections_per_ip),
+            ("max_connects_per_minute", &mut flood.max_connects_per_minute),
+        ] {
+            let key_path = format!("{}.{}", path, key);
+            if let Some(max) = self.optional_uint(&node[key], &key_path) {
+                if max == 0 {
+                    self.errors.push(format!("{}: deve ser maior que zero", key_path));
+                }
+                *value = max;
+            }
+        }
+        if let Some(minutes) = self.optional_u64(&node["ban_minutes"], &format!("{}.ban_minutes", path)) {
+            if minutes > MAX_BAN_MINUTES {
+                self.errors.push(format!(
+                    "{}.ban_minutes: {} acima do máximo de {}",
+                    path, minutes, MAX_BAN_MINUTES
+                ));
+            }
+            flood.ban_minutes = minutes;
+        }
+
+        flood
+    }
+
+    fn proxy(&mut self, node: &Yaml, path: &str) -> ProxyConfig {
+        let mut proxy = ProxyConfig::default();
+        if node.is_badvalue() || node.is_null() {
+            return proxy;
+        }
+        if !node.is_hash() {
+            self.errors.push(format!("{}: esperava um mapa", path));
+            return proxy;
+        }
+        self.check_keys(node, path, &["trusted", "header"]);
+
+        match &node["trusted"] {
+            Yaml::BadValue | Yaml::Null => {}
+            Yaml::Array(nodes) => {
+                for (index, node) in nodes.iter().enumerate() {
+                    let item_path = format!("{}.trusted[{}]", path, index);
+                    let Some(address) = self.required_str(node, &item_path) else {
+                        continue;
+                    };
+                    match address.parse::<IpAddr>() {
+                        Ok(address) => proxy.trusted.push(address),
+                        Err(_) => self
+                            .errors
+                            .push(format!("{}: endereço IP inválido '{}'", item_path, address)),
+                    }
+                }
+            }
+            other => self.errors.push(format!(
+                "{}.trusted: esperava uma lista de endereços, encontrou {}",
+                path,
+                yaml_display(other)
+            )),
+        }
+        if !node["header"].is_badvalue() && !node["header"].is_null() {
+            if let Some(header) = self.required_str(&node["header"], &format!("{}.header", path)) {
+                proxy.header = header;
+            }
+        }
+
+        proxy
+    }
+
+    fn station(&mut self, node: &Yaml, path: &str, radio_seed: u64) -> Option<StationConfig> {
+        if !node.is_hash() {
+            self.errors.push(format!("{}: esperava um mapa", path));
+            return None;
+        }
+        self.check_keys(
+            node,
+            path,
+            &[
+                "name",
+                "directory",
+                "playlist",
+                "seed",
+                "frequency",
+                "max_listeners",
+                "reserved_slots",
+                "fallback_mount",
+                "private",
+                "buffer",
+                "codecs",
+                "default_codec",
+                "hls",
+                "dash",
+                "websocket",
+            ],
+        );
+
+        let name = self.required_str(&node["name"], &format!("{}.name", path));
+        if let Some(name) = &name {
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                self.errors.push(format!(
+                    "{}.name: '{}' deve ter apenas letras, números, '-' e '_'",
+                    path, name
+                ));
+            }
+        }
+
+        let directory = self
+            .required_str(&node["directory"], &format!("{}.directory", path))
+            .map(|directory| self.base_directory.join(directory));
+        if let Some(directory) = &directory {
+            if !directory.is_dir() {
+                self.errors.push(format!(
+                    "{}.directory: diretório {} não existe",
+                    path,
+                    directory.display()
+                ));
+            }
+        }
+
+        let playlist = match &node["playlist"] {
+            Yaml::BadValue | Yaml::Null => None,
+            value => self
+                .required_str(value, &format!("{}.playlist", path))
+                .zip(directory.as_ref())
+                .map(|(playlist, directory)| directory.join(playlist)),
+        };
+        match (&playlist, &directory) {
+            (Some(playlist), _) if !playlist.is_file() => self.errors.push(format!(
+                "{}.playlist: arquivo {} não existe",
+                path,
+                playlist.display()
+            )),
+            (None, Some(directory)) if directory.is_dir() && !directory.join("metadata.json").is_file() => {
+                self.errors.push(format!(
+                    "{}: sem playlist, é preciso um metadata.json em {}",
+                    path,
+                    directory.display()
+                ))
+            }
+            _ => {}
+        }
+
+        let seed = self
+            .optional_u64(&node["seed"], &format!("{}.seed", path))
+            .unwrap_or(radio_seed);
+
+        // conferida já em f32, que é como a estação guarda: um f64 grande demais vira infinito
+        let frequency = self
+            .required_f64(&node["frequency"], &format!("{}.frequency", path))
+            .map(|frequency| frequency as f32);
+        if let Some(frequency) = frequency {
+            if !(frequency.is_finite() && frequency > 0.0) {
+                self.errors
+                    .push(format!("{}.frequency: deve ser um número positivo", path));
+            }
+        }
+
+        let max_listeners = self
+            .optional_uint(&node["max_listeners"], &format!("{}.max_listeners", path))
+            .unwrap_or(MAX_STATION_LISTENERS);
+        if max_listeners == 0 {
+            self.errors
+                .push(format!("{}.max_listeners: deve ser maior que zero", path));
+        }
+
+        let reserved_slots = self
+            .optional_uint(&node["reserved_slots"], &format!("{}.reserved_slots", path))
+            .unwrap_or(0);
+        if reserved_slots >= max_listeners && max_listeners > 0 {
+            self.errors.push(format!(
+                "{}.reserved_slots: {} deve ser menor que max_listeners ({})",
+                path, reserved_slots, max_listeners
+            ));
+        }
+
+        let fallback_mount = match &node["fallback_mount"] {
+            Yaml::BadValue | Yaml::Null => None,
+            value => self.required_str(value, &format!("{}.fallback_mount", path)),
+        };
+
+        let private = match &node["private"] {
+            Yaml::BadValue | Yaml::Null => false,
+            Yaml::Boolean(private) => *private,
+            other => {
+                self.errors.push(format!(
+                    "{}.private: esperava true ou false, encontrou {}",
+                    path,
+                    yaml_display(other)
+                ));
+                false
+            }
+        };
+
+        let buffer = self.buffer(&node["buffer"], &format!("{}.buffer", path));
+        let codecs = self.codecs(&node["codecs"], &format!("{}.codecs", path), max_listeners);
+        let default_codec = self.default_codec(
+            &node["default_codec"],
+            &format!("{}.default_codec", path),
+            &codecs,
+        );
+        let hls = self.segmented(
+            &node["hls"],
+            &format!("{}.hls", path),
+            &HlsCodec::ALL.map(|codec| codec.name()),
+            |name| HlsCodec::from_name(name).map(|codec| (codec, codec.default_bitrate_kbps())),
+        );
+        let dash = self.segmented(
+            &node["dash"],
+            &format!("{}.dash", path),
+            &DashCodec::ALL.map(|codec| codec.name()),
+            |name| DashCodec::from_name(name).map(|codec| (codec, codec.default_bitrate_kbps())),
+        );
+        let websocket = self.websocket(&node["websocket"], &format!("{}.websocket", path), max_listeners);
+
+        Some(StationConfig {
+            name: name?,
+            directory: directory?,
+            playlist,
+            seed,
+            frequency: frequency?,
+            max_listeners,
+            reserved_slots,
+            fallback_mount,
+            private,
+            buffer,
+            codecs,
+            default_codec: default_codec?,
+            hls,
+            dash,
+            websocket,
+        })
+    }
+
+    fn default_codec(&mut self, node: &Yaml, path: &str, codecs: &[CodecConfig]) -> Option<OutputCodec> {
+        if node.is_badvalue() || node.is_null() {
+            return codecs.first().map(|c| c.codec.clone());
+        }
+
+        let name = self.required_str(node, path)?;
+        match codecs.iter().find(|c| c.codec.name().eq_ignore_ascii_case(&name)) {
+            Some(codec_config) => Some(codec_config.codec.clone()),
+            None => {
+                self.errors.push(format!(
+                    "{}: '{}' não está entre os codecs da estação",
+                    path, name
+                ));
+                None
+            }
+        }
+    }
+
+    /// `hls: aac` ou `hls: { codec: aac, bitrate: 96, segment_seconds: 6, window: 6 }`, e o mesmo para `dash`.
+    /// `codec` devolve o codec com esse nome e o bitrate padrão dele
+    fn segmented<C>(
+        &mut self,
+        node: &Yaml,
+        path: &str,
+        known: &[&str],
+        codec: impl Fn(&str) -> Option<(C, u32)>,
+    ) -> Option<SegmentedConfig<C>> {
+        let (codec_node, bitrate_node, segment_node, window_node) = match node {
+            Yaml::BadValue | Yaml::Null => return None,
+            Yaml::Hash(_) => {
+                self.check_keys(node, path, &["codec", "bitrate", "segment_seconds", "window"]);
+                (&node["codec"], &node["bitrate"], &node["segment_seconds"], &node["window"])
+            }
+            _ => (node, &Yaml::BadValue, &Yaml::BadValue, &Yaml::BadValue),
+        };
+
+        let codec_name = self.required_str(codec_node, &format!("{}.codec", path))?;
+        let Some((codec, default_bitrate_kbps)) = codec(&codec_name) else {
+            self.errors.push(format!(
+                "{}.codec: codec desconhecido '{}' (conhecidos: {})",
+                path,
+                codec_name,
+                known.join(", ")
+            ));
+            return None;
+        };
+
+        let bitrate_kbps = self
+            .optional_uint(bitrate_node, &format!("{}.bitrate", path))
+            .unwrap_or(default_bitrate_kbps);
+        if !(MIN_BITRATE_KBPS..=MAX_BITRATE_KBPS).contains(&bitrate_kbps) {
+            self.errors.push(format!(
+                "{}.bitrate: {} kbps fora do intervalo {}..={}",
+                path, bitrate_kbps, MIN_BITRATE_KBPS, MAX_BITRATE_KBPS
+            ));
+        }
+
+        let segment_seconds = self
+            .optional_uint(segment_node, &format!("{}.segment_seconds", path))
+            .unwrap_or(DEFAULT_SEGMENT_SECONDS);
+        if !SEGMENT_SECONDS.contains(&segment_seconds) {
+            self.errors.push(format!(
+                "{}.segment_seconds: {} fora do intervalo {}..={}",
+                path,
+                segment_seconds,
+                SEGMENT_SECONDS.start(),
+                SEGMENT_SECONDS.end()
+            ));
+        }
+
+        let window = self
+            .optional_uint(window_node, &format!("{}.window", path))
+            .unwrap_or(DEFAULT_SEGMENT_WINDOW);
+        if !SEGMENT_WINDOW.contains(&window) {
+            self.errors.push(format!(
+                "{}.window: {} segmentos fora do intervalo {}..={}",
+                path,
+                window,
+                SEGMENT_WINDOW.start(),
+                SEGMENT_WINDOW.end()
+            ));
+        }
+
+        Some(SegmentedConfig {
+            codec,
+            bitrate_kbps,
+            segment_seconds,
+            window,
+        })
+    }
+
+    /// `websocket: true` ou `websocket: { bitrate: 64, max_listeners: 50 }`
+    fn websocket(&mut self, node: &Yaml, path: &str, station_max_listeners: usize) -> Option<WebSocketConfig> {
+        let (bitrate_node, max_listeners_node) = match node {
+            Yaml::BadValue | Yaml::Null | Yaml::Boolean(false) => return None,
+            Yaml::Boolean(true) => (&Yaml::BadValue, &Yaml::BadValue),
+            Yaml::Hash(_) => {
+                self.check_keys(node, path, &["bitrate", "max_listeners"]);
+                (&node["bitrate"], &node["max_listeners"])
+            }
+            other => {
+                self.errors.push(format!(
+                    "{}: esperava true, false ou um mapa, encontrou {}",
+                    path,
+                    yaml_display(other)
+                ));
+                return None;
+            }
+        };
+
+        let bitrate_kbps = self
+            .optional_uint(bitrate_node, &format!("{}.bitrate", path))
+            .unwrap_or(websocket::DEFAULT_BITRATE_KBPS);
+        if !(MIN_BITRATE_KBPS..=MAX_BITRATE_KBPS).contains(&bitrate_kbps) {
+            self.errors.push(format!(
+                "{}.bitrate: {} kbps fora do intervalo {}..={}",
+                path, bitrate_kbps, MIN_BITRATE_KBPS, MAX_BITRATE_KBPS
+            ));
+        }
+
+        let max_listeners = self
+            .optional_uint(max_listeners_node, &format!("{}.max_listeners", path))
+            .unwrap_or(station_max_listeners);
+        if max_listeners == 0 || max_listeners > station_max_listeners {
+            self.errors.push(format!(
+                "{}.max_listeners: {} deve estar entre 1 e o max_listeners da estação ({})",
+                path, max_listeners, station_max_listeners
+            ));
+        }
+
+        Some(WebSocketConfig {
+            bitrate_kbps,
+            max_listeners,
+        })
+    }
+
+    fn buffer(&mut self, node: &Yaml, path: &str) -> BufferConfig {
+        let mut buffer = BufferConfig::default();
+        if node.is_badvalue() || node.is_null() {
+            return buffer;
+        }
+        if !node.is_hash() {
+            self.errors.push(format!("{}: esperava um mapa", path));
+            return buffer;
+        }
+        self.check_keys(node, path, &["setpoint_high", "setpoint_low"]);
+
+        if let Some(high) = self.optional_uint(&node["setpoint_high"], &format!("{}.setpoint_high", path)) {
+            buffer.setpoint_high = high;
+        }
+        if let Some(low) = self.optional_uint(&node["setpoint_low"], &format!("{}.setpoint_low", path)) {
+            buffer.setpoint_low = low;
+        }
+
+        if buffer.setpoint_low >= buffer.setpoint_high {
+            self.errors.push(format!(
+                "{}: setpoint_low ({}) deve ser menor que setpoint_high ({})",
+                path, buffer.setpoint_low, buffer.setpoint_high
+            ));
+        }
+
+        buffer
+    }
+
+    fn codecs(&mut self, node: &Yaml, path: &str, station_max_listeners: usize) -> Vec<CodecConfig> {
+        let Some(nodes) = node.as_vec().filter(|nodes| !nodes.is_empty()) else {
+            self.errors
+                .push(format!("{}: é preciso pelo menos um codec de saída", path));
+            return Vec::new();
+        };
+
+        let mut codecs: Vec<CodecConfig> = Vec::new();
+        for (index, node) in nodes.iter().enumerate() {
+            let path = format!("{}[{}]", path, index);
+
+            // aceita tanto `- mp3` quanto `- { codec: mp3, bitrate: 128, max_listeners: 32 }`
+            let (codec_node, bitrate_node, max_listeners_node) = if node.is_hash() {
+                self.check_keys(node, &path, &["codec", "bitrate", "max_listeners"]);
+                (&node["codec"], &node["bitrate"], &node["max_listeners"])
+            } else {
+                (node, &Yaml::BadValue, &Yaml::BadValue)
+            };
+
+            let Some(codec_name) = self.required_str(codec_node, &format!("{}.codec", path)) else {
+                continue;
+            };
+            let Some(codec) = OutputCodec::from_name(&codec_name) else {
+                let known: Vec<&str> = OutputCodec::ALL.iter().map(|c| c.name()).collect();
+                self.errors.push(format!(
+                    "{}.codec: codec desconhecido '{}' (conhecidos: {})",
+                    path,
+                    codec_name,
+                    known.join(", ")
+                ));
+                continue;
+            };
+
+            let bitrate_kbps = self
+                .optional_uint(bitrate_node, &format!("{}.bitrate", path));
+            let bitrate_kbps = match (bitrate_kbps, codec.default_bitrate_kbps()) {
+                (Some(_), None) => {
+                    self.errors.push(format!(
+                        "{}.bitrate: {} é sem perdas e não tem bitrate",
+                        path,
+                        codec.name()
+                    ));
+                    None
+                }
+                (bitrate_kbps, default_bitrate_kbps) => bitrate_kbps.or(default_bitrate_kbps),
+            };
+            let out_of_range = |kbps: &u32| !(MIN_BITRATE_KBPS..=MAX_BITRATE_KBPS).contains(kbps);
+            if let Some(bitrate_kbps) = bitrate_kbps.filter(out_of_range) {
+                self.errors.push(format!(
+                    "{}.bitrate: {} kbps fora do intervalo {}..={}",
+                    path, bitrate_kbps, MIN_BITRATE_KBPS, MAX_BITRATE_KBPS
+                ));
+            }
+
+            let max_listeners = self
+                .optional_uint(max_listeners_node, &format!("{}.max_listeners", path))
+                .unwrap_or(station_max_listeners);
+            if max_listeners == 0 || max_listeners > station_max_listeners {
+                self.errors.push(format!(
+                    "{}.max_listeners: {} deve estar entre 1 e o max_listeners da estação ({})",
+                    path, max_listeners, station_max_listeners
+                ));
+            }
+
+            if codecs.iter().any(|c| c.codec == codec) {
+                self.errors
+                    .push(format!("{}.codec: codec {} repetido", path, codec.name()));
+                continue;
+            }
+
+            codecs.push(CodecConfig {
+                codec,
+                bitrate_kbps,
+                max_listeners,
+            });
+        }
+
+        codecs
+    }
+
+    /// Reporta chaves desconhecidas, que quase sempre são erros de digitação
+    fn check_keys(&mut self, node: &Yaml, path: &str, allowed: &[&str]) {
+        let Some(hash) = node.as_hash() else {
+            return;
+        };
+
+        for key in hash.keys() {
+            let known = key.as_str().is_some_and(|key| allowed.contains(&key));
+            if !known {
+                let prefix = if path.is_empty() { String::new() } else { format!("{}.", path) };
+                self.errors.push(format!(
+                    "{}{}: chave desconhecida (esperava uma de: {})",
+                    prefix,
+                    yaml_display(key),
+                    allowed.join(", ")
+                ));
+            }
+        }
+    }
+
+    fn required_str(&mut self, node: &Yaml, path: &str) -> Option<String> {
+        match node {
+            Yaml::String(value) => Some(value.clone()),
+            Yaml::BadValue | Yaml::Null => {
+                self.errors.push(format!("{}: campo obrigatório", path));
+                None
+            }
+            other => {
+                self.errors
+                    .push(format!("{}: esperava texto, encontrou {}", path, yaml_display(other)));
+                None
+            }
+        }
+    }
+
+    fn required_f64(&mut self, node: &Yaml, path: &str) -> Option<f64> {
+        match node {
+            Yaml::Real(_) => node.as_f64(),
+            Yaml::Integer(value) => Some(*value as f64),
+            Yaml::BadValue | Yaml::Null => {
+                self.errors.push(format!("{}: campo obrigatório", path));
+                None
+            }
+            other => {
+                self.errors
+                    .push(format!("{}: esperava um número, encontrou {}", path, yaml_display(other)));
+                None
+            }
+        }
+    }
+
+    fn optional_u64(&mut self, node: &Yaml, path: &str) -> Option<u64> {
+        match node {
+            Yaml::BadValue | Yaml::Null => None,
+            Yaml::Integer(value) if *value >= 0 => Some(*value as u64),
+            other => {
+                self.errors.push(format!(
+                    "{}: esperava um inteiro não negativo, encontrou {}",
+                    path,
+                    yaml_display(other)
+                ));
+                None
+            }
+        }
+    }
+
+    /// Como `optional_u64`, mas o valor precisa caber em `T`; não é truncado antes das checagens de intervalo
+    fn optional_uint<T: TryFrom<u64>>(&mut self, node: &Yaml, path: &str) -> Option<T> {
+        let value = self.optional_u64(node, path)?;
+        match T::try_from(value) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.errors.push(format!("{}: {} é grande demais", path, value));
+                None
+            }
+        }
+    }
+}
+
+fn yaml_display(node: &Yaml) -> String {
+    match node {
+        Yaml::String(value) | Yaml::Real(value) => format!("'{}'", value),
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Array(_) => "uma lista".to_owned(),
+        Yaml::Hash(_) => "um mapa".to_owned(),
+        _ => "nada".to_owned(),
+    }
+}
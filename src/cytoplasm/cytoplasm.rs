@@ -1,124 +1,461 @@
 use std::{
     collections::{HashMap, VecDeque},
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
     thread::{self},
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use tokio::sync::broadcast as tbroadcast;
+use tracing::{debug, error, info, info_span, warn};
+use web_radio::objects::{
+    station::{
+        history::{StationHistory, HISTORY_FILE_NAME},
+        likes::LikeBook,
+        sessions::{SessionLog, SESSION_LOG_FILE_NAME},
+        station::Station,
+        station_state::{Buffering, OnAir},
+    },
+    track::{
+        audience::AudienceBook,
+        charts::{PlayLog, PlayRecord, PLAY_LOG_FILE_NAME},
+        track::Track,
+    },
 };
 
 use crate::{
-    input_decoder::input_audio_file::{self, AudioPacket},
-    output_encoder::audio_encoder::{AudioEncoder, OutputCodec},
-    output_stream::OutputStream,
+    audio_file_info,
+    config::{BufferConfig, StationConfig, WebSocketConfig},
+    cytoplasm::playout::{
+        AudienceSampler, NowPlaying, PlayoutCounters, QueuedPacket, ScheduledTrack, TrackSchedule,
+    },
+    input_decoder::input_audio_file,
+    output_encoder::audio_encoder::{ffmpeg_args, AudioEncoder, EncodedOutput, OutputCodec},
+    output_stream::{
+        capacity::ListenerLimits,
+        dash::DashStream,
+        flac::FlacOutput,
+        hls::HlsStream,
+        token::{StreamAccess, StreamSigner},
+        websocket::{self, WebSocketOutput},
+        OutputStream,
+    },
 };
 
 const FUCKALL_DURATION: Duration = Duration::from_millis(5);
-const SETPOINT_HIGH: usize = 10;
-const SETPOINT_LOW: usize = 5;
+/// quanto esperar antes de tentar a próxima faixa, quando o arquivo da atual sumiu
+const MISSING_TRACK_BACKOFF: Duration = Duration::from_secs(1);
+pub const SETPOINT_HIGH: usize = 10;
+pub const SETPOINT_LOW: usize = 5;
+
+/// buffer de pacotes PCM entre a thread do decoder e a dos encoders
+type PacketBuffer = Arc<Mutex<VecDeque<QueuedPacket>>>;
 
 pub struct Cytoplasm {
-    encoders: Arc<Mutex<HashMap<OutputCodec, AudioEncoder>>>,
+    /// a estação como o `Radio` a conhece; o estado dela acompanha o buffer
+    pub station: Arc<Mutex<Station>>,
+    encoders: Arc<Mutex<Vec<AudioEncoder>>>,
     pub output_streams: Arc<HashMap<OutputCodec, Arc<OutputStream>>>,
+    /// codecs na ordem em que foram configurados, que é a ordem de preferência da estação
+    pub codecs: Vec<OutputCodec>,
+    pub default_codec: OutputCodec,
+    /// mount para onde mandamos os ouvintes quando a estação está cheia
+    pub fallback_mount: Option<String>,
+    /// saída HLS, se a estação tiver uma
+    pub hls: Option<Arc<HlsStream>>,
+    /// saída MPEG-DASH, se a estação tiver uma
+    pub dash: Option<Arc<DashStream>>,
+    /// saída WebSocket, se a estação tiver uma
+    pub websocket: Option<Arc<WebSocketOutput>>,
+    /// faixa no ar agora; `None` até o buffer encher pela primeira vez
+    pub now_playing: Arc<RwLock<Option<NowPlaying>>>,
+    /// um evento por troca de faixa
+    pub track_events: tbroadcast::Sender<NowPlaying>,
+    pub likes: Mutex<LikeBook>,
+    /// pico e média de ouvintes por faixa, atualizados ao fim de cada execução
+    pub audience: Arc<Mutex<AudienceBook>>,
+    /// um `StationSnapshot` por faixa tocada
+    pub history: Arc<StationHistory>,
+    /// uma `PlayRecord` por execução completa, para as paradas
+    pub plays: Arc<PlayLog>,
+    /// uma `SessionRecord` por ouvinte que desconectou
+    pub sessions: Arc<SessionLog>,
+    /// underruns da thread dos encoders
+    pub counters: Arc<PlayoutCounters>,
+    buffer: PacketBuffer,
 }
 
-impl Cytoplasm {
-    pub fn new(station_directory: PathBuf, output_codecs: &[OutputCodec]) -> Cytoplasm {
-        let buffer = Arc::new(Mutex::new(VecDeque::<AudioPacket>::new()));
-        let output_streams = Self::init_output_streams(&output_codecs);
-        let encoders = Self::init_encoders(&output_codecs, &output_streams);
-
-        Self::init_decoder_thread(station_directory.clone(), buffer.clone());
-        Self::init_encoder_thread(encoders.clone(), buffer.clone());
+/// quem a thread dos encoders avisa quando a faixa no ar muda
+struct OnAirWatchers {
+    station: Arc<Mutex<Station>>,
+    hls: Option<Arc<HlsStream>>,
+    now_playing: Arc<RwLock<Option<NowPlaying>>>,
+    track_events: tbroadcast::Sender<NowPlaying>,
+    audience: Arc<Mutex<AudienceBook>>,
+    history: Arc<StationHistory>,
+    plays: Arc<PlayLog>,
+    counters: Arc<PlayoutCounters>,
+}
 
+impl Cytoplasm {
+    pub fn new(
+        config: &StationConfig,
+        station: Arc<Mutex<Station>>,
+        connections: Arc<AtomicUsize>,
+        likes: LikeBook,
+        audience: AudienceBook,
+        signer: Option<StreamSigner>,
+    ) -> Cytoplasm {
+        let audience = Arc::new(Mutex::new(audience));
+        let history = Arc::new(StationHistory::new(&config.directory.join(HISTORY_FILE_NAME)));
+        let plays = Arc::new(PlayLog::new(&config.directory.join(PLAY_LOG_FILE_NAME)));
+        let sessions = Arc::new(SessionLog::new(&config.directory.join(SESSION_LOG_FILE_NAME)));
+        let buffer: PacketBuffer = Arc::new(Mutex::new(VecDeque::new()));
+        let access = StreamAccess {
+            signer,
+            private: config.private,
+        };
+        let hls = config
+            .hls
+            .as_ref()
+            .map(|hls_config| Arc::new(HlsStream::new(&config.name, hls_config, access.clone())));
+        let dash = config
+            .dash
+            .as_ref()
+            .map(|dash_config| Arc::new(DashStream::new(&config.name, dash_config, access.clone())));
+        let websocket = config.websocket.as_ref().map(|websocket_config| {
+            let stream = Self::init_websocket_stream(
+                config,
+                websocket_config,
+                &station,
+                &connections,
+                access.clone(),
+                &sessions,
+            );
+            Arc::new(WebSocketOutput::new(stream))
+        });
+        let output_streams = Self::init_output_streams(config, &station, &connections, access, &sessions);
+        let encoders = Self::init_encoders(
+            config,
+            &output_streams,
+            hls.as_ref(),
+            dash.as_ref(),
+            websocket.as_ref(),
+        );
+        // todos os mounts com ouvintes: os streams HTTP e o WebSocket
+        let mounts = output_streams
+            .values()
+            .chain(websocket.as_ref().map(|websocket| websocket.stream()))
+            .cloned()
+            .collect();
         let output_streams_arc = Arc::new(output_streams);
-
-        Self::init_reporting_thread(output_streams_arc.clone());
+        let now_playing = Arc::new(RwLock::new(None));
+        let (track_events, _) = tbroadcast::channel(16);
+        let counters = Arc::new(PlayoutCounters::default());
+
+        Self::init_decoder_thread(
+            station.clone(),
+            config.directory.clone(),
+            config.seed,
+            config.buffer,
+            buffer.clone(),
+        );
+        Self::init_encoder_thread(
+            encoders.clone(),
+            mounts,
+            OnAirWatchers {
+                station: station.clone(),
+                hls: hls.clone(),
+                now_playing: now_playing.clone(),
+                track_events: track_events.clone(),
+                audience: audience.clone(),
+                history: history.clone(),
+                plays: plays.clone(),
+                counters: counters.clone(),
+            },
+            config.buffer,
+            buffer.clone(),
+        );
 
         return Cytoplasm {
+            station,
             output_streams: output_streams_arc,
+            codecs: config.codecs.iter().map(|c| c.codec.clone()).collect(),
+            default_codec: config.default_codec.clone(),
+            fallback_mount: config.fallback_mount.clone(),
+            hls,
+            dash,
+            websocket,
+            now_playing,
+            track_events,
+            likes: Mutex::new(likes),
+            audience,
+            history,
+            plays,
+            sessions,
+            counters,
+            buffer,
             encoders,
         };
     }
 
-    fn init_output_streams(codecs: &[OutputCodec]) -> HashMap<OutputCodec, Arc<OutputStream>> {
+    /// Os mounts com ouvintes: os streams HTTP, na ordem de preferência da estação, e o WebSocket, se houver
+    pub fn mounts(&self) -> Vec<&Arc<OutputStream>> {
+        self.codecs
+            .iter()
+            .filter_map(|codec| self.output_streams.get(codec))
+            .chain(self.websocket.as_ref().map(|websocket| websocket.stream()))
+            .collect()
+    }
+
+    /// Total de ouvintes conectados, somando todos os mounts da estação
+    pub fn listener_count(&self) -> usize {
+        self.mounts()
+            .iter()
+            .map(|stream| stream.client_count())
+            .sum()
+    }
+
+    /// Pacotes PCM (~1s cada) esperando a thread dos encoders
+    pub fn buffer_depth(&self) -> usize {
+        self.buffer.lock().unwrap().len()
+    }
+
+    fn init_output_streams(
+        config: &StationConfig,
+        registry: &Arc<Mutex<Station>>,
+        connections: &Arc<AtomicUsize>,
+        access: StreamAccess,
+        sessions: &Arc<SessionLog>,
+    ) -> HashMap<OutputCodec, Arc<OutputStream>> {
         let mut streams = HashMap::new();
 
-        for codec in codecs {
-            let stream = OutputStream::new(codec.clone());
-            streams.insert(codec.clone(), Arc::new(stream));
+        for codec_config in &config.codecs {
+            let limits = ListenerLimits {
+                station_max: config.max_listeners,
+                codec_max: codec_config.max_listeners,
+                reserved_slots: config.reserved_slots,
+            };
+            let stream = OutputStream::new(
+                &config.name,
+                codec_config.codec.clone(),
+                registry.clone(),
+                limits,
+                access.clone(),
+                connections.clone(),
+                sessions.clone(),
+            );
+            streams.insert(codec_config.codec.clone(), Arc::new(stream));
         }
 
         streams
     }
 
-    /// cria e inicializa um encoder de áudio para cada codec de saída solicitado
+    /// O mount `estação/ws`, com limite próprio e as mesmas regras de acesso dos streams HTTP
+    fn init_websocket_stream(
+        config: &StationConfig,
+        websocket_config: &WebSocketConfig,
+        registry: &Arc<Mutex<Station>>,
+        connections: &Arc<AtomicUsize>,
+        access: StreamAccess,
+        sessions: &Arc<SessionLog>,
+    ) -> OutputStream {
+        let limits = ListenerLimits {
+            station_max: config.max_listeners,
+            codec_max: websocket_config.max_listeners,
+            reserved_slots: config.reserved_slots,
+        };
+        OutputStream::new(
+            &config.name,
+            OutputCodec::Opus128kbps,
+            registry.clone(),
+            limits,
+            access,
+            connections.clone(),
+            sessions.clone(),
+        )
+        .renamed("ws")
+    }
+
+    /// cria e inicializa um encoder de áudio para cada codec de saída solicitado, mais um para cada uma das
+    /// saídas HLS, DASH e WebSocket
     fn init_encoders(
-        codecs: &[OutputCodec],
+        config: &StationConfig,
         streams: &HashMap<OutputCodec, Arc<OutputStream>>,
-    ) -> Arc<Mutex<HashMap<OutputCodec, AudioEncoder>>> {
-        let mut encoders = HashMap::new();
-        for codec in codecs {
+        hls: Option<&Arc<HlsStream>>,
+        dash: Option<&Arc<DashStream>>,
+        websocket: Option<&Arc<WebSocketOutput>>,
+    ) -> Arc<Mutex<Vec<AudioEncoder>>> {
+        let mut encoders = Vec::new();
+        for codec_config in &config.codecs {
+            let codec = &codec_config.codec;
             let output_stream = streams.get(codec).unwrap().clone();
-            let encoder = AudioEncoder::new(&codec, output_stream);
-            encoders.insert(codec.clone(), encoder);
+            // o FLAC passa pelo framer, que guarda o cabeçalho para quem conectar depois
+            let output: Arc<dyn EncodedOutput> = match codec {
+                OutputCodec::Flac => Arc::new(FlacOutput::new(output_stream)),
+                _ => output_stream,
+            };
+            let args = ffmpeg_args(codec, codec_config.bitrate_kbps);
+            encoders.push(AudioEncoder::new(args, output));
+        }
+        if let (Some(hls), Some(hls_config)) = (hls, &config.hls) {
+            let args = hls_config.codec.ffmpeg_args(hls_config.bitrate_kbps);
+            encoders.push(AudioEncoder::new(args, hls.clone()));
+        }
+        if let (Some(dash), Some(dash_config)) = (dash, &config.dash) {
+            let args = dash_config
+                .codec
+                .ffmpeg_args(dash_config.bitrate_kbps, dash_config.segment_seconds);
+            encoders.push(AudioEncoder::new(args, dash.clone()));
+        }
+        if let (Some(websocket), Some(websocket_config)) = (websocket, &config.websocket) {
+            let args = websocket::ffmpeg_args(websocket_config.bitrate_kbps);
+            encoders.push(AudioEncoder::new(args, websocket.clone()));
         }
         Arc::new(Mutex::new(encoders))
     }
 
     /// inicia a thread responsável por decodificar arquivos de áudio
     /// ela carrega trilhas conforme definidas e enfileira pacotes no buffer compartilhado
-    fn init_decoder_thread(station_directory: PathBuf, buffer: Arc<Mutex<VecDeque<AudioPacket>>>) {
-        thread::spawn(move || loop {
-            let next_track = station_directory.join("bicameral_mind.mp3");
-
-            eprintln!(
-                "cytoplasm/d: abrindo arquivo: {}",
-                next_track.to_str().unwrap()
+    fn init_decoder_thread(
+        station: Arc<Mutex<Station>>,
+        station_directory: PathBuf,
+        seed: u64,
+        buffer_config: BufferConfig,
+        buffer: PacketBuffer,
+    ) {
+        let span = info_span!("decoder", station = %station.lock().unwrap().name);
+        thread::spawn(move || {
+            let _span = span.entered();
+            // varrer a biblioteca da estação; só arquivos novos ou modificados passam pelo ffprobe
+            let library = audio_file_info::scan_library(&station_directory);
+            let total_milliseconds: u64 = library.iter().map(|f| f.audio_milliseconds()).sum();
+            info!(
+                files = library.len(),
+                minutes = format!("{:.1}", total_milliseconds as f64 / 60_000.0),
+                "biblioteca varrida"
             );
 
-            let file =
-                input_audio_file::open_input_file_strategy(next_track.to_str().unwrap().to_owned());
-            for packet in file {
-                let mut buf_guard = buffer.lock().unwrap();
-                if buf_guard.len() >= SETPOINT_HIGH {
-                    // eprintln!("cytoplasm/d: Backpressure! Pausando encoder...");
-
-                    drop(buf_guard); // liberar mutex imediatamente
-
-                    // fazer porra nenhuma até o buffer estar quase vazio
-                    'backpressure: loop {
-                        thread::sleep(FUCKALL_DURATION);
-                        let buf_guard = buffer.lock().unwrap();
-                        if buf_guard.len() <= SETPOINT_LOW {
-                            // eprintln!("cytoplasm/d: Backpressure acabou!");
-                            break 'backpressure;
-                        }
-                    }
-
-                    // finalmente continuar enfileirando pacotes
-                    buffer.lock().unwrap().push_back(packet);
-                } else {
-                    // enfileirar pacote imediatamente; ainda cabe no buffer
-                    buf_guard.push_back(packet);
+            // faixas sem duração conhecida (ex.: playlist sem #EXTINF) pegam a duração do probe
+            let mut tracks = station.lock().unwrap().tracks.clone();
+            for track in tracks.iter_mut().filter(|track| track.duration == 0) {
+                let location = station_directory.join(&track.source);
+                if let Some(info) = library.iter().find(|info| info.location() == location) {
+                    track.duration = (info.audio_milliseconds() / 1000) as u32;
                 }
             }
+            // a API de faixas lê da estação, então ela também precisa das durações
+            station.lock().unwrap().tracks = tracks.clone();
+
+            Self::decoder_loop(station_directory, tracks, seed, buffer_config, buffer);
         });
     }
 
+    fn decoder_loop(
+        station_directory: PathBuf,
+        tracks: Vec<Track>,
+        seed: u64,
+        buffer_config: BufferConfig,
+        buffer: PacketBuffer
+    ) -> ! {
+        // a próxima faixa é escolhida assim que a atual começa, para sabermos o que vem a seguir
+        let mut schedule = TrackSchedule::new(tracks, seed);
+        let mut track = schedule.next_track();
+        loop {
+            let next = schedule.next_track();
+            let location = station_directory.join(&track.source);
+            let scheduled = Arc::new(ScheduledTrack {
+                track,
+                next: next.clone(),
+            });
+
+            Self::decode_track(&location, scheduled, buffer_config, &buffer);
+            track = next;
+        }
+    }
+
+    /// decodifica uma faixa inteira para o buffer, respeitando os setpoints de backpressure
+    fn decode_track(
+        location: &Path,
+        scheduled: Arc<ScheduledTrack>,
+        buffer_config: BufferConfig,
+        buffer: &PacketBuffer,
+    ) {
+        if !location.is_file() {
+            warn!(file = %location.display(), "arquivo sumiu, pulando faixa");
+            thread::sleep(MISSING_TRACK_BACKOFF);
+            return;
+        }
+
+        debug!(file = %location.display(), "abrindo arquivo");
+
+        let file = input_audio_file::open_input_file_strategy(location.to_string_lossy().into_owned());
+        for packet in file {
+            let packet = QueuedPacket {
+                scheduled: scheduled.clone(),
+                packet,
+            };
+            let mut buf_guard = buffer.lock().unwrap();
+            if buf_guard.len() >= buffer_config.setpoint_high {
+                // eprintln!("cytoplasm/d: Backpressure! Pausando encoder...");
+
+                drop(buf_guard); // liberar mutex imediatamente
+
+                // fazer porra nenhuma até o buffer estar quase vazio
+                'backpressure: loop {
+                    thread::sleep(FUCKALL_DURATION);
+                    let buf_guard = buffer.lock().unwrap();
+                    if buf_guard.len() <= buffer_config.setpoint_low {
+                        // eprintln!("cytoplasm/d: Backpressure acabou!");
+                        break 'backpressure;
+                    }
+                }
+
+                // finalmente continuar enfileirando pacotes
+                buffer.lock().unwrap().push_back(packet);
+            } else {
+                // enfileirar pacote imediatamente; ainda cabe no buffer
+                buf_guard.push_back(packet);
+            }
+        }
+    }
+
     /// inicia a thread que consome pacotes do buffer, envia para os encoders e mantém o timing de reprodução
     fn init_encoder_thread(
-        encoders: Arc<Mutex<HashMap<OutputCodec, AudioEncoder>>>,
-        buffer: Arc<Mutex<VecDeque<AudioPacket>>>,
+        encoders: Arc<Mutex<Vec<AudioEncoder>>>,
+        mounts: Vec<Arc<OutputStream>>,
+        watchers: OnAirWatchers,
+        buffer_config: BufferConfig,
+        buffer: PacketBuffer,
     ) {
+        let OnAirWatchers {
+            station,
+            hls,
+            now_playing,
+            track_events,
+            audience,
+            history,
+            plays,
+            counters,
+        } = watchers;
+        // faixa que está sendo entregue aos encoders agora, e a audiência dela até aqui
+        let mut on_air: Option<Arc<ScheduledTrack>> = None;
+        let mut sampler = AudienceSampler::default();
+
+        let span = info_span!("playout", station = %station.lock().unwrap().name);
         thread::spawn(move || loop {
-            fn block_until_buffer_full(buffer: &Arc<Mutex<VecDeque<AudioPacket>>>) {
+            let _span = span.enter();
+            fn block_until_buffer_full(buffer: &PacketBuffer, buffer_config: BufferConfig) {
                 // fazer porra nenhuma até o buffer estar cheio
                 loop {
                     thread::sleep(FUCKALL_DURATION);
                     let guard = buffer.lock().unwrap();
-                    if guard.len() >= SETPOINT_HIGH {
+                    if guard.len() >= buffer_config.setpoint_high {
                         // finalmente buffer cheio; a outra thread deve ter printado "BACKPRESSURE!!"
-                        eprintln!("cytoplasm/e: Buffering alcançado!");
+                        info!("buffering alcançado");
                         break;
                     }
                 }
@@ -126,7 +463,8 @@ impl Cytoplasm {
 
             // inicialmente vamos deixar o buffer encher completamente, antes de começar a consumi-lo
             // isso previne underruns durante o setup
-            block_until_buffer_full(&buffer);
+            block_until_buffer_full(&buffer, buffer_config);
+            station.lock().unwrap().change_state(Box::new(OnAir));
 
             let start = Instant::now();
             let mut playback_time = 0.0;
@@ -134,9 +472,12 @@ impl Cytoplasm {
             loop {
                 let mut buf_guard = buffer.lock().unwrap();
                 if buf_guard.len() == 0 {
-                    eprintln!("cytoplasm/e: Underrun...");
+                    warn!("underrun: buffer vazio, voltando a bufferizar");
+                    counters.underruns.fetch_add(1, Ordering::Relaxed);
                     drop(buf_guard);
-                    block_until_buffer_full(&buffer);
+                    station.lock().unwrap().change_state(Box::new(Buffering));
+                    block_until_buffer_full(&buffer, buffer_config);
+                    station.lock().unwrap().change_state(Box::new(OnAir));
                 } else {
                     // consumir todo o áudio da fila
                     let mut consumed_audio = Vec::new();
@@ -148,11 +489,77 @@ impl Cytoplasm {
                     // liberar mutex para que possam continuar enfileirando pacotes na outra thread
                     drop(buf_guard);
 
+                    // uma amostra de ouvintes por iteração; vale para todo o áudio consumido nela
+                    let listeners: usize = mounts
+                        .iter()
+                        .map(|stream| stream.client_count())
+                        .sum();
+
                     // transmitir o áudio para todos os encoders, dar sleep
                     let mut encoders_guard = encoders.lock().unwrap();
-                    for packet in consumed_audio {
+                    for QueuedPacket { scheduled, packet } in consumed_audio {
+                        // primeiro pacote de uma faixa nova: avisar quem precisa saber o que está no ar
+                        if !on_air.as_ref().is_some_and(|current| Arc::ptr_eq(current, &scheduled)) {
+                            if let Some(previous) = on_air.take() {
+                                let finished = std::mem::take(&mut sampler);
+                                if let Err(e) = audience.lock().unwrap().record_play(
+                                    &previous.track.source,
+                                    finished.peak_listeners(),
+                                    finished.average_listeners(),
+                                ) {
+                                    error!("{}", e);
+                                }
+
+                                // o now_playing ainda é o da faixa que acabou
+                                let started_at = now_playing
+                                    .read()
+                                    .unwrap()
+                                    .as_ref()
+                                    .map_or(UNIX_EPOCH, |playing| playing.started_at);
+                                let record = PlayRecord {
+                                    source: previous.track.source.clone(),
+                                    started_at: started_at
+                                        .duration_since(UNIX_EPOCH)
+                                        .map_or(0, |d| d.as_secs() as i64),
+                                    seconds: finished.seconds(),
+                                    peak_listeners: finished.peak_listeners(),
+                                    average_listeners: finished.average_listeners(),
+                                };
+                                if let Err(e) = plays.append(&record) {
+                                    error!("{}", e);
+                                }
+                            }
+
+                            let on_air_at = start + Duration::from_secs_f64(playback_time);
+                            let playing = NowPlaying::new(&scheduled, on_air_at);
+
+                            let title = playing.stream_title();
+                            info!(title = %title, "no ar");
+                            for stream in &mounts {
+                                stream.set_stream_title(title.clone());
+                            }
+                            if let Some(hls) = &hls {
+                                hls.set_track(&scheduled.track);
+                            }
+
+                            let snapshot = station.lock().unwrap().snapshot(
+                                &scheduled.track,
+                                listeners,
+                                playing.started_at,
+                            );
+                            if let Err(e) = history.append(&snapshot) {
+                                error!("{}", e);
+                            }
+
+                            *now_playing.write().unwrap() = Some(playing.clone());
+                            // sem ninguém inscrito o send falha, e tudo bem
+                            let _ = track_events.send(playing);
+                            on_air = Some(scheduled);
+                        }
+
+                        sampler.sample(listeners, packet.audio_length);
                         playback_time += packet.audio_length;
-                        for encoder in encoders_guard.values_mut() {
+                        for encoder in encoders_guard.iter_mut() {
                             encoder.push_audio_packet(packet.clone());
                         }
                     }
@@ -166,47 +573,11 @@ impl Cytoplasm {
                     if next_time > now {
                         thread::sleep(next_time - now);
                     } else {
-                        eprintln!("cytoplasm/e: Time underrun...");
+                        warn!("time underrun: a iteração terminou depois do horário do áudio");
+                        counters.time_underruns.fetch_add(1, Ordering::Relaxed);
                     }
                 }
             }
         });
     }
-
-    fn init_reporting_thread(streams: Arc<HashMap<OutputCodec, Arc<OutputStream>>>) {
-        thread::spawn(move || {
-            let mut last_bytes = HashMap::new();
-            let mut last_time = Instant::now();
-
-            loop {
-                for (codec, stream) in streams.iter() {
-                    let mut bytes_total = 0usize;
-
-                    for (bytes, _) in stream.get_bandwidth_stats().values() {
-                        bytes_total += bytes;
-                    }
-
-                    let elapsed_secs = last_time.elapsed().as_secs_f64();
-                    let kbps = if let Some(prev_bytes) = last_bytes.get(codec) {
-                        let delta_bytes = bytes_total.saturating_sub(*prev_bytes) as f64;
-                        delta_bytes / (1024.0 * elapsed_secs)
-                    } else {
-                        0.0
-                    };
-
-                    last_bytes.insert(codec.clone(), bytes_total);
-
-                    eprintln!(
-                        "cytoplasm: stats: {} clientes, {:.2} KB enviados, {:.2} kb/s",
-                        stream.list_clients().len(),
-                        bytes_total as f64 / 1024.0,
-                        kbps
-                    );
-                }
-
-                last_time = Instant::now();
-                thread::sleep(Duration::from_secs(2));
-            }
-        });
-    }
 }
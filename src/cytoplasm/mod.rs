@@ -1 +1,2 @@
 pub mod cytoplasm;
+pub mod playout;
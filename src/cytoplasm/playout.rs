@@ -0,0 +1,137 @@
+use std::{
+    sync::{atomic::AtomicUsize, Arc},
+    time::{Instant, SystemTime},
+};
+
+use web_radio::objects::track::{track::Track, track_iterator::TrackIterator};
+
+use crate::input_decoder::input_audio_file::AudioPacket;
+
+/// Uma faixa da programação, junto com a que já foi escolhida para tocar depois dela
+pub struct ScheduledTrack {
+    pub track: Track,
+    pub next: Track,
+}
+
+/// Pacote PCM no buffer entre o decoder e os encoders, marcado com a faixa de onde ele veio.
+/// É assim que a thread dos encoders sabe o que está no ar.
+pub struct QueuedPacket {
+    pub scheduled: Arc<ScheduledTrack>,
+    pub packet: AudioPacket,
+}
+
+/// O que está tocando agora numa estação
+#[derive(Clone)]
+pub struct NowPlaying {
+    pub track: Track,
+    pub next: Track,
+    /// quando a faixa começou a ser transmitida
+    pub started_at: SystemTime,
+}
+
+impl NowPlaying {
+    /// `on_air_at` é o instante (monotônico) em que o primeiro pacote da faixa entra no ar; pode estar um pouco no futuro,
+    /// já que a thread dos encoders empurra o buffer inteiro de uma vez
+    pub fn new(scheduled: &ScheduledTrack, on_air_at: Instant) -> NowPlaying {
+        let now = Instant::now();
+        let started_at = if on_air_at >= now {
+            SystemTime::now() + (on_air_at - now)
+        } else {
+            SystemTime::now() - (now - on_air_at)
+        };
+
+        NowPlaying {
+            track: scheduled.track.clone(),
+            next: scheduled.next.clone(),
+            started_at,
+        }
+    }
+
+    /// Título no formato "Artista - Título", usado no StreamTitle do ICY
+    pub fn stream_title(&self) -> String {
+        if self.track.artist.is_empty() {
+            self.track.title.clone()
+        } else {
+            format!("{} - {}", self.track.artist, self.track.title)
+        }
+    }
+}
+
+/// Amostra o número de ouvintes enquanto uma faixa toca. Cada amostra pesa pelo tempo de áudio que ela cobre,
+/// então a média não depende de quantos pacotes cada iteração da thread dos encoders consome.
+#[derive(Default)]
+pub struct AudienceSampler {
+    peak_listeners: usize,
+    listener_seconds: f64,
+    seconds: f64,
+}
+
+impl AudienceSampler {
+    pub fn sample(&mut self, listeners: usize, seconds: f64) {
+        self.peak_listeners = self.peak_listeners.max(listeners);
+        self.listener_seconds += listeners as f64 * seconds;
+        self.seconds += seconds;
+    }
+
+    /// segundos de áudio amostrados
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    pub fn peak_listeners(&self) -> usize {
+        self.peak_listeners
+    }
+
+    pub fn average_listeners(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.listener_seconds / self.seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Contadores da thread dos encoders, expostos em `/metrics`
+#[derive(Default)]
+pub struct PlayoutCounters {
+    /// vezes que o buffer esvaziou e a estação voltou a bufferizar
+    pub underruns: AtomicUsize,
+    /// iterações que terminaram depois do horário do áudio que entregaram
+    pub time_underruns: AtomicUsize,
+}
+
+/// Programação infinita de uma estação: toca cada faixa uma vez por ciclo (via `TrackIterator`)
+/// e começa um ciclo novo, com outra semente, quando as faixas acabam
+pub struct TrackSchedule {
+    tracks: Vec<Track>,
+    seed: u64,
+    cycle: u64,
+    iterator: TrackIterator,
+    started: bool,
+}
+
+impl TrackSchedule {
+    pub fn new(tracks: Vec<Track>, seed: u64) -> TrackSchedule {
+        let iterator = TrackIterator::new(tracks.clone(), seed);
+
+        TrackSchedule {
+            tracks,
+            seed,
+            cycle: 0,
+            iterator,
+            started: false,
+        }
+    }
+
+    pub fn next_track(&mut self) -> Track {
+        if !self.started {
+            self.started = true;
+        } else if self.iterator.go_next().is_err() {
+            self.cycle += 1;
+            self.iterator =
+                TrackIterator::new(self.tracks.clone(), self.seed.wrapping_add(self.cycle));
+        }
+
+        self.iterator.get_current().clone()
+    }
+}
@@ -5,6 +5,7 @@ use std::{
 };
 
 use bytes::Bytes;
+use tracing::trace;
 
 use crate::input_decoder::input_audio_file::calculate_buffer_length;
 
@@ -78,7 +79,7 @@ impl Iterator for ComplexCodecFile {
             .expect("complex_codec_file: Falha ao ler bytes");
 
         if n == 0 {
-            println!("complex_codec_file: EOF.");
+            trace!("complex_codec_file: EOF");
             return None;
         }
 
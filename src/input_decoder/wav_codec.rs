@@ -5,6 +5,7 @@ use std::{
 };
 
 use bytes::Bytes;
+use tracing::trace;
 
 use super::input_audio_file::{
     calculate_buffer_length, AudioFile, AudioPacket, BYTE_DEPTH, CHANNEL_COUNT, SAMPLE_RATE,
@@ -66,7 +67,7 @@ impl Iterator for WavCodecFile {
             .expect("wav_codec: Audio file is unreadable");
 
         if bytes_read == 0 {
-            println!("wav_codec: EOF.");
+            trace!("wav_codec: EOF");
             return None;
         }
 
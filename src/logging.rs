@@ -0,0 +1,32 @@
+// logs estruturados: nível por módulo, campos (estação, codec, cliente) e saída em texto ou JSON.
+// Os logs do Rocket passam pelo mesmo filtro, com o alvo `rocket`
+
+use std::io::IsTerminal;
+
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Variável de ambiente com diretivas no formato do `RUST_LOG`; tem precedência sobre a configuração
+pub const LOG_ENV_VAR: &str = "WEB_RADIO_LOG";
+
+/// Instala o subscriber global. Chamar uma segunda vez não faz nada
+pub fn init(config: &LoggingConfig) {
+    let filter = match std::env::var(LOG_ENV_VAR) {
+        Ok(directives) => EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+            eprintln!("logging: {} inválido ({}), usando a configuração", LOG_ENV_VAR, e);
+            EnvFilter::new(config.directives())
+        }),
+        Err(_) => EnvFilter::new(config.directives()),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+
+    let _ = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    };
+}
@@ -1,16 +1,45 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime},
+};
 
+use api::likes::LikeThrottle;
 use bytes::Bytes;
+use config::{LoggingConfig, RadioConfig, DEFAULT_CONFIG_PATH};
 use cytoplasm::cytoplasm::Cytoplasm;
 use output_encoder::audio_encoder::OutputCodec;
+use output_stream::{
+    bans::{BanList, NotBanned},
+    client::ClientIdentity,
+    dash::DashStream,
+    flood::{ConnectionPermit, FloodGuard},
+    hls::HlsStream,
+    icy::IcyMetadataRequest,
+    negotiation::{negotiate_codec, ClientHints},
+    token::{StreamSigner, TokenError},
+    websocket::{WebSocketSession, WebSocketUpgrade},
+    OutputStream, SegmentRefused, SegmentResponse, StreamRefused, StreamResponse,
+};
 use rocket::{
-    http::ContentType,
-    response::{content::RawHtml, stream::ByteStream},
+    fairing::AdHoc,
+    futures::Stream,
+    http::Header,
+    response::{content::RawHtml, status::NotFound, Redirect},
+};
+use tracing::{error, info};
+use web_radio::objects::{
+    radio::Radio,
+    station::likes::{LikeBook, LIKES_FILE_NAME},
+    track::audience::{AudienceBook, AUDIENCE_FILE_NAME},
 };
 
+pub mod api;
 pub mod audio_file_info;
+pub mod config;
 pub mod cytoplasm;
 pub mod input_decoder;
+pub mod logging;
 pub mod output_encoder;
 pub mod output_stream;
 
@@ -18,35 +47,440 @@ pub mod output_stream;
 extern crate rocket;
 
 #[get("/")]
-fn index() -> RawHtml<&'static [u8]> {
-    return RawHtml(b"<!DOCTYPE html>\n<audio controls src='/station'>");
+fn index(state: &rocket::State<StationMap>) -> RawHtml<String> {
+    let mut names: Vec<&String> = state.keys().collect();
+    names.sort();
+
+    let mut html = String::from("<!DOCTYPE html>\n");
+    for name in names {
+        let mut mounts: Vec<&str> = state[name]
+            .output_streams
+            .values()
+            .map(|stream| stream.mount())
+            .collect();
+        mounts.sort();
+
+        for mount in mounts {
+            html += &format!("<p>{}</p><audio controls preload='none' src='/station/{}'></audio>\n", mount, mount);
+        }
+        if state[name].hls.is_some() {
+            html += &format!(
+                "<p>{}/hls</p><audio controls preload='none' src='/station/{}/hls/live.m3u8'></audio>\n",
+                name, name
+            );
+        }
+        if state[name].dash.is_some() {
+            // <audio> não toca DASH sozinho; o link serve para colar no dash.js ou no ExoPlayer
+            html += &format!(
+                "<p>{}/dash</p><a href='/station/{}/dash/live.mpd'>live.mpd</a>\n",
+                name, name
+            );
+        }
+    }
+
+    RawHtml(html)
 }
 
-type StationMap = HashMap<String, Cytoplasm>;
+pub type StationMap = HashMap<String, Cytoplasm>;
+
+/// limites do `Retry-After` de uma estação cheia, em segundos
+const RETRY_AFTER_MIN: u64 = 5;
+const RETRY_AFTER_MAX: u64 = 60;
+
+/// por que um stream não foi aberto
+#[derive(Responder)]
+enum StreamRejection {
+    #[response(status = 404)]
+    NotFound(String),
+    /// estação privada e sem token
+    #[response(status = 401)]
+    Unauthorized(String),
+    /// token inválido, expirado ou de outra estação
+    #[response(status = 403)]
+    Forbidden(String),
+    /// estação cheia e sem um fallback que aceite o ouvinte
+    #[response(status = 503)]
+    Full(String, Header<'static>),
+    /// estação cheia: vai para o mount de fallback
+    Redirect(Box<Redirect>),
+    /// HLS ainda sem segmentos suficientes
+    #[response(status = 503)]
+    NotReady(String, Header<'static>),
+    /// pedido comum numa rota que só aceita WebSocket
+    #[response(status = 426)]
+    UpgradeRequired(String, Header<'static>),
+}
+
+impl From<NotFound<String>> for StreamRejection {
+    fn from(not_found: NotFound<String>) -> Self {
+        StreamRejection::NotFound(not_found.0)
+    }
+}
+
+fn stream_refused(state: &StationMap, station: &Cytoplasm, name: &str, refused: StreamRefused) -> StreamRejection {
+    match refused {
+        StreamRefused::Full => station_full(state, station, name),
+        StreamRefused::Unauthorized(TokenError::Missing) => {
+            StreamRejection::Unauthorized(TokenError::Missing.to_string())
+        }
+        StreamRefused::Unauthorized(error) => StreamRejection::Forbidden(error.to_string()),
+    }
+}
+
+/// Estação cheia: manda para o fallback, se houver e se ele aceitaria o ouvinte (não é privado nem está cheio
+/// também), ou pede para tentar de novo quando a faixa atual acabar, que é quando ouvintes costumam sair
+fn station_full(state: &StationMap, station: &Cytoplasm, name: &str) -> StreamRejection {
+    let fallback = station.fallback_mount.as_ref().filter(|fallback| {
+        fallback
+            .split_once('/')
+            .and_then(|(target, codec)| find_output_stream(state, target, codec).ok())
+            .is_some_and(|stream| stream.accepts_anonymous())
+    });
+    if let Some(fallback) = fallback {
+        let redirect = Redirect::temporary(format!("/station/{}", fallback));
+        return StreamRejection::Redirect(Box::new(redirect));
+    }
+
+    let remaining = station
+        .now_playing
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|playing| {
+            let duration = Duration::from_secs(playing.track.duration as u64);
+            (playing.started_at + duration).duration_since(SystemTime::now()).ok()
+        })
+        .map_or(RETRY_AFTER_MAX, |remaining| remaining.as_secs());
+    let retry_after = remaining.clamp(RETRY_AFTER_MIN, RETRY_AFTER_MAX);
+
+    StreamRejection::Full(
+        format!("a estação {} está cheia", name),
+        Header::new("Retry-After", retry_after.to_string()),
+    )
+}
+
+/// `output` é o nome da saída (HLS, DASH) para a mensagem; `retry_after` é a duração de um segmento
+fn segment_refused(output: &str, name: &str, retry_after: u32, refused: SegmentRefused) -> StreamRejection {
+    match refused {
+        SegmentRefused::Unauthorized(TokenError::Missing) => {
+            StreamRejection::Unauthorized(TokenError::Missing.to_string())
+        }
+        SegmentRefused::Unauthorized(error) => StreamRejection::Forbidden(error.to_string()),
+        SegmentRefused::NotReady => StreamRejection::NotReady(
+            format!("o {} da estação {} ainda está bufferizando", output, name),
+            Header::new("Retry-After", retry_after.to_string()),
+        ),
+        SegmentRefused::UnknownSegment => {
+            StreamRejection::NotFound("segmento desconhecido ou fora da janela".to_owned())
+        }
+    }
+}
+
+/// acha a saída HLS de uma estação, ou um 404 explicando o que não existe
+fn find_hls<'a>(state: &'a StationMap, name: &str) -> Result<&'a HlsStream, NotFound<String>> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+
+    station
+        .hls
+        .as_deref()
+        .ok_or_else(|| NotFound(format!("a estação {} não transmite em HLS", name)))
+}
+
+/// playlist HLS com a janela dos últimos segmentos; os players a pedem de novo a cada segmento
+#[get("/station/<name>/hls/live.m3u8?<token>")]
+fn hls_playlist(
+    name: &str,
+    token: Option<&str>,
+    _not_banned: NotBanned,
+    state: &rocket::State<StationMap>,
+) -> Result<SegmentResponse, StreamRejection> {
+    let hls = find_hls(state, name)?;
+
+    hls.playlist(token)
+        .map_err(|refused| segment_refused("HLS", name, hls.segment_seconds(), refused))
+}
+
+#[get("/station/<name>/hls/<segment>?<token>", rank = 2)]
+fn hls_segment(
+    name: &str,
+    segment: &str,
+    token: Option<&str>,
+    _not_banned: NotBanned,
+    state: &rocket::State<StationMap>,
+) -> Result<SegmentResponse, StreamRejection> {
+    let hls = find_hls(state, name)?;
+
+    hls.segment(segment, token)
+        .map_err(|refused| segment_refused("HLS", name, hls.segment_seconds(), refused))
+}
 
-#[get("/station")]
-fn station_endpoint(state: &rocket::State<StationMap>) -> (ContentType, ByteStream![Bytes]) {
-    let station = state.get("diamondcityradio").unwrap();
-    let stream = station
+/// acha a saída DASH de uma estação, ou um 404 explicando o que não existe
+fn find_dash<'a>(state: &'a StationMap, name: &str) -> Result<&'a DashStream, NotFound<String>> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+
+    station
+        .dash
+        .as_deref()
+        .ok_or_else(|| NotFound(format!("a estação {} não transmite em DASH", name)))
+}
+
+/// manifesto DASH dinâmico; os players o pedem de novo a cada `minimumUpdatePeriod`
+#[get("/station/<name>/dash/live.mpd?<token>")]
+fn dash_manifest(
+    name: &str,
+    token: Option<&str>,
+    _not_banned: NotBanned,
+    state: &rocket::State<StationMap>,
+) -> Result<SegmentResponse, StreamRejection> {
+    let dash = find_dash(state, name)?;
+
+    dash.manifest(token)
+        .map_err(|refused| segment_refused("DASH", name, dash.segment_seconds(), refused))
+}
+
+/// `init.mp4` ou `<número>.m4s`
+#[get("/station/<name>/dash/<segment>?<token>", rank = 2)]
+fn dash_segment(
+    name: &str,
+    segment: &str,
+    token: Option<&str>,
+    _not_banned: NotBanned,
+    state: &rocket::State<StationMap>,
+) -> Result<SegmentResponse, StreamRejection> {
+    let dash = find_dash(state, name)?;
+
+    dash.segment(segment, token)
+        .map_err(|refused| segment_refused("DASH", name, dash.segment_seconds(), refused))
+}
+
+/// acha o stream de saída de uma estação em um codec, ou um 404 explicando o que não existe
+fn find_output_stream<'a>(
+    state: &'a StationMap,
+    name: &str,
+    codec: &str,
+) -> Result<&'a OutputStream, NotFound<String>> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+    let codec = OutputCodec::from_name(codec)
+        .ok_or_else(|| NotFound(format!("codec desconhecido: {}", codec)))?;
+
+    station
         .output_streams
-        .get(&OutputCodec::Mp3_64kbps)
-        .unwrap();
+        .get(&codec)
+        .map(|stream| stream.as_ref())
+        .ok_or_else(|| {
+            NotFound(format!(
+                "a estação {} não transmite em {}",
+                name,
+                codec.name()
+            ))
+        })
+}
+
+/// pacotes Opus com sequência e timestamp, para players com Web Audio; o protocolo está em `output_stream::websocket`
+#[get("/station/<name>/ws")]
+fn station_websocket(
+    name: &str,
+    upgrade: Option<WebSocketUpgrade>,
+    permit: ConnectionPermit,
+    client: ClientIdentity,
+    state: &rocket::State<StationMap>,
+) -> Result<WebSocketSession, StreamRejection> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+    let websocket = station
+        .websocket
+        .as_ref()
+        .ok_or_else(|| NotFound(format!("a estação {} não transmite por WebSocket", name)))?;
+    let upgrade = upgrade.ok_or_else(|| {
+        StreamRejection::UpgradeRequired(
+            "esta rota só aceita conexões WebSocket".to_owned(),
+            Header::new("Upgrade", "websocket"),
+        )
+    })?;
+
+    let admission = websocket
+        .stream()
+        .admit(client, permit)
+        .map_err(|refused| stream_refused(state, station, name, refused))?;
+    Ok(websocket.session(admission, upgrade))
+}
 
-    stream.create_consumer_http_stream()
+// rank 2: `/station/<name>/now` e as outras rotas da API têm precedência sobre o codec
+#[get("/station/<name>/<codec>", rank = 2)]
+fn station_endpoint(
+    name: &str,
+    codec: &str,
+    permit: ConnectionPermit,
+    client: ClientIdentity,
+    icy: IcyMetadataRequest,
+    state: &rocket::State<StationMap>,
+) -> Result<StreamResponse<impl Stream<Item = Bytes>>, StreamRejection> {
+    let stream = find_output_stream(state, name, codec)?;
+
+    stream
+        .create_consumer_http_stream(client, permit, icy.0)
+        .map_err(|refused| stream_refused(state, &state[name], name, refused))
+}
+
+/// escolhe o codec pelo `?format=`, pelo header Accept ou pelo User-Agent, caindo no codec padrão da estação
+#[get("/station/<name>?<format>")]
+fn station_negotiated_endpoint(
+    name: &str,
+    format: Option<&str>,
+    hints: ClientHints,
+    permit: ConnectionPermit,
+    client: ClientIdentity,
+    icy: IcyMetadataRequest,
+    state: &rocket::State<StationMap>,
+) -> Result<StreamResponse<impl Stream<Item = Bytes>>, StreamRejection> {
+    let station = state
+        .get(name)
+        .ok_or_else(|| NotFound(format!("estação desconhecida: {}", name)))?;
+    let codec = negotiate_codec(&station.codecs, &station.default_codec, format, &hints)
+        .map_err(StreamRejection::NotFound)?;
+
+    let mut response = find_output_stream(state, name, codec.name())?
+        .create_consumer_http_stream(client, permit, icy.0)
+        .map_err(|refused| stream_refused(state, station, name, refused))?;
+    response
+        .headers
+        .push(Header::new("X-Codec", codec.name()));
+    response
+        .headers
+        .push(Header::new("Vary", "Accept, User-Agent"));
+
+    Ok(response)
+}
+
+/// carrega a configuração e liga os logs, encerrando o processo com a lista de erros se ela for inválida
+fn load_config() -> RadioConfig {
+    let config_path =
+        std::env::var("WEB_RADIO_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_owned());
+
+    match RadioConfig::load(Path::new(&config_path)) {
+        Ok(config) => {
+            logging::init(&config.logging);
+            config
+        }
+        Err(errors) => {
+            // sem configuração válida, os erros saem com os níveis padrão
+            logging::init(&LoggingConfig::default());
+            error!(path = %config_path, "configuração inválida");
+            for problem in errors {
+                error!(path = %config_path, "  - {}", problem);
+            }
+            std::process::exit(1);
+        }
+    }
 }
 
 #[launch]
 fn rocket() -> _ {
+    let config = load_config();
+
+    let mut radio = Radio::new(config.seed, String::new());
+    let signer = config.tokens.secret.as_deref().map(StreamSigner::new);
     let mut stations: StationMap = HashMap::new();
-    stations.insert(
-        "diamondcityradio".to_string(),
-        Cytoplasm::new(
-            PathBuf::from("./DiamondCityRadio"),
-            &[OutputCodec::Mp3_64kbps],
-        ),
-    );
+    for station_config in &config.stations {
+        let station = station_config.load_station().unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+
+        let codecs: Vec<&str> = station_config.codecs.iter().map(|c| c.codec.name()).collect();
+        info!(
+            station = %station.name,
+            frequency = station.frequency,
+            tracks = station.tracks.len(),
+            codecs = %codecs.join(", "),
+            max_listeners = station_config.max_listeners,
+            "estação configurada"
+        );
+
+        let likes = LikeBook::load(&station_config.directory.join(LIKES_FILE_NAME))
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+
+        let audience = AudienceBook::load(&station_config.directory.join(AUDIENCE_FILE_NAME))
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+
+        let station = radio.add_station(station);
+        stations.insert(
+            station_config.name.clone(),
+            Cytoplasm::new(
+                station_config,
+                station,
+                radio.connections.clone(),
+                likes,
+                audience,
+                signer.clone(),
+            ),
+        );
+    }
 
     rocket::build()
+        .manage(radio)
+        .manage(config.admin.clone())
+        .manage(config.tokens.clone())
+        .manage(BanList::default())
+        .manage(FloodGuard::new(config.flood))
+        .manage(config.proxy.clone())
+        .manage(LikeThrottle::default())
         .manage(stations)
-        .mount("/", routes![index, station_endpoint])
+        // encerra os streams antes de desligar, para que as sessões abertas sejam gravadas
+        .attach(AdHoc::on_shutdown("encerra as sessões", |rocket| {
+            Box::pin(async move {
+                if let Some(stations) = rocket.state::<StationMap>() {
+                    for stream in stations.values().flat_map(|cytoplasm| cytoplasm.mounts()) {
+                        stream.close_all();
+                    }
+                }
+            })
+        }))
+        .mount(
+            "/",
+            routes![
+                index,
+                station_endpoint,
+                station_negotiated_endpoint,
+                station_websocket,
+                hls_playlist,
+                hls_segment,
+                dash_manifest,
+                dash_segment
+            ],
+        )
+        .mount(
+            "/",
+            routes![
+                api::now_playing::now_playing,
+                api::now_playing::now_playing_events,
+                api::stations::stations,
+                api::metrics::metrics,
+                api::likes::like_station,
+                api::likes::like_now_playing,
+                api::library::library,
+                api::ranking::ranking,
+                api::history::history,
+                api::charts::station_charts,
+                api::charts::radio_charts,
+                api::admin::list_clients,
+                api::admin::kick_client,
+                api::admin::mint_token,
+                api::sessions::sessions,
+                api::sessions::report
+            ],
+        )
 }
@@ -0,0 +1,26 @@
+// estado pequeno guardado em JSON entre execuções (likes, audiência, cache de probes)
+
+use std::{fs, io::ErrorKind, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Lê o JSON de `path`; se o arquivo ainda não existir, começa do `T::default()`
+pub fn load_json_or_default<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            serde_json::from_str(&content).map_err(|e| format!("{} inválido: {}", path.display(), e))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("falha ao ler {}: {}", path.display(), e)),
+    }
+}
+
+/// Grava num arquivo temporário e renomeia, para não deixar um JSON pela metade se o processo cair
+pub fn save_json_atomically<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_string(value).map_err(|e| format!("falha ao serializar: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+
+    fs::write(&tmp_path, content)
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|e| format!("falha ao gravar {}: {}", path.display(), e))
+}
@@ -1,4 +1,5 @@
 
+pub mod json_store;
 pub mod radio;
 pub mod station;
 pub mod track;
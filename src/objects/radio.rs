@@ -1,27 +1,45 @@
 use std::collections::HashMap;
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex};
 
-use rand::SeedableRng;
-use rocket::time::Date;
 use super::{station::station::Station};
 
-struct Radio {
-    pub stations: HashMap<String, Station>,
+pub struct Radio {
+    pub stations: HashMap<String, Arc<Mutex<Station>>>,
     pub seed: u64,
-    pub connections: i64,
+    // total de conexões abertas em todas as estações; compartilhado com os streams de saída
+    pub connections: Arc<AtomicUsize>,
     pub _frequency: String
 }
 
 impl Radio {
 
-    fn new(seed: u64, _frequency: String) -> Self {
+    pub fn new(seed: u64, _frequency: String) -> Self {
         Self {
             stations: HashMap::new(),
             seed,
-            connections: 0,
+            connections: Arc::new(AtomicUsize::new(0)),
             _frequency,
         }
     }
 
+    /// Registra a estação pelo nome e devolve a referência compartilhada com quem for tocá-la
+    pub fn add_station(&mut self, station: Station) -> Arc<Mutex<Station>> {
+        let station = Arc::new(Mutex::new(station));
+        self.stations.insert(station.lock().unwrap().name.clone(), station.clone());
+        station
+    }
+
+    /// Nomes das estações em ordem alfabética
+    pub fn station_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.stations.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
     fn determine_station() {
         // recebe o endpoint aqui e aponta para o spawn correto
     }
@@ -38,4 +56,4 @@ impl Radio {
         // pega o endpoint e trata
     }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,76 @@
+// histórico de execuções de uma estação: um StationSnapshot por linha (JSONL), só acrescentado
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use rocket::time::{Date, Time};
+
+use super::station_snapshot::StationSnapshot;
+
+pub const HISTORY_FILE_NAME: &str = ".history.jsonl";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub struct StationHistory {
+    path: PathBuf,
+}
+
+impl StationHistory {
+    pub fn new(path: &Path) -> StationHistory {
+        StationHistory {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Acrescenta o snapshot no fim do log. Cada linha é escrita de uma vez, então um crash
+    /// no meio perde no máximo a última linha
+    pub fn append(&self, snapshot: &StationSnapshot) -> Result<(), String> {
+        let mut line = serde_json::to_string(snapshot)
+            .map_err(|e| format!("history: falha ao serializar: {}", e))?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("history: falha ao gravar {}: {}", self.path.display(), e))
+    }
+
+    /// Snapshots com `from <= created_at < to` (segundos Unix), do mais antigo para o mais novo.
+    /// Linhas corrompidas são ignoradas
+    pub fn range(&self, from: i64, to: i64) -> Result<Vec<StationSnapshot>, String> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("history: falha ao abrir {}: {}", self.path.display(), e)),
+        };
+
+        let mut snapshots = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("history: falha ao ler {}: {}", self.path.display(), e))?;
+            if let Ok(snapshot) = serde_json::from_str::<StationSnapshot>(&line) {
+                if snapshot.created_at >= from && snapshot.created_at < to {
+                    snapshots.push(snapshot);
+                }
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Tudo o que tocou num dia (UTC)
+    pub fn day(&self, date: Date) -> Result<Vec<StationSnapshot>, String> {
+        let (from, to) = day_bounds(date);
+        self.range(from, to)
+    }
+}
+
+/// Início e fim (exclusivo) de um dia UTC, em segundos Unix
+pub fn day_bounds(date: Date) -> (i64, i64) {
+    let from = date.with_time(Time::MIDNIGHT).assume_utc().unix_timestamp();
+    (from, from + SECONDS_PER_DAY)
+}
@@ -0,0 +1,125 @@
+// likes dos ouvintes nas faixas e na estação, persistidos em JSON no diretório da estação
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::objects::json_store::{load_json_or_default, save_json_atomically};
+
+pub const LIKES_FILE_NAME: &str = ".likes.json";
+
+/// Quem curtiu o quê, e quando (segundos Unix). Guardamos os ouvintes (e não só a contagem) para que cada
+/// um curta uma vez só, mesmo depois de reiniciar o servidor.
+#[derive(Default, Serialize, Deserialize)]
+struct LikeData {
+    #[serde(deserialize_with = "station_likers")]
+    station: BTreeMap<String, i64>,
+    /// chave: `Track.source`
+    #[serde(deserialize_with = "track_likers")]
+    tracks: BTreeMap<String, BTreeMap<String, i64>>,
+}
+
+/// Os ouvintes de um like como estão no arquivo. Os arquivos antigos guardavam só o conjunto de ouvintes, sem o
+/// instante; esses likes são lidos como dados no instante 0
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredLikers {
+    Dated(BTreeMap<String, i64>),
+    Undated(BTreeSet<String>),
+}
+
+impl From<StoredLikers> for BTreeMap<String, i64> {
+    fn from(likers: StoredLikers) -> Self {
+        match likers {
+            StoredLikers::Dated(likers) => likers,
+            StoredLikers::Undated(listeners) => listeners.into_iter().map(|listener| (listener, 0)).collect(),
+        }
+    }
+}
+
+fn station_likers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, i64>, D::Error> {
+    StoredLikers::deserialize(deserializer).map(Into::into)
+}
+
+fn track_likers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, BTreeMap<String, i64>>, D::Error> {
+    let tracks = BTreeMap::<String, StoredLikers>::deserialize(deserializer)?;
+    Ok(tracks.into_iter().map(|(source, likers)| (source, likers.into())).collect())
+}
+
+pub struct LikeBook {
+    path: PathBuf,
+    data: LikeData,
+}
+
+impl LikeBook {
+    /// Carrega os likes de `path`; se o arquivo não existir começa do zero
+    pub fn load(path: &Path) -> Result<LikeBook, String> {
+        let data = load_json_or_default(path).map_err(|e| format!("likes: {}", e))?;
+
+        Ok(LikeBook {
+            path: path.to_path_buf(),
+            data,
+        })
+    }
+
+    /// Curte a faixa. Retorna `false` se esse ouvinte já tinha curtido.
+    pub fn like_track(&mut self, source: &str, listener: &str) -> Result<bool, String> {
+        let likes = self.data.tracks.entry(source.to_owned()).or_default();
+        let added = !likes.contains_key(listener);
+        if added {
+            likes.insert(listener.to_owned(), unix_now());
+            self.save()?;
+        }
+        Ok(added)
+    }
+
+    /// Curte a estação. Retorna `false` se esse ouvinte já tinha curtido.
+    pub fn like_station(&mut self, listener: &str) -> Result<bool, String> {
+        let added = !self.data.station.contains_key(listener);
+        if added {
+            self.data.station.insert(listener.to_owned(), unix_now());
+            self.save()?;
+        }
+        Ok(added)
+    }
+
+    pub fn track_likes(&self, source: &str) -> usize {
+        self.data.tracks.get(source).map_or(0, |listeners| listeners.len())
+    }
+
+    pub fn station_likes(&self) -> usize {
+        self.data.station.len()
+    }
+
+    /// Likes de cada faixa dados com `from <= instante < to` (segundos Unix); faixas sem likes no intervalo ficam de fora
+    pub fn track_likes_between(&self, from: i64, to: i64) -> BTreeMap<&str, usize> {
+        self.data
+            .tracks
+            .iter()
+            .map(|(source, listeners)| {
+                let count = listeners
+                    .values()
+                    .filter(|liked_at| **liked_at >= from && **liked_at < to)
+                    .count();
+                (source.as_str(), count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_json_atomically(&self.path, &self.data).map_err(|e| format!("likes: {}", e))
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
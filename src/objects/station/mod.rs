@@ -1,3 +1,6 @@
+pub mod history;
+pub mod likes;
+pub mod sessions;
 pub mod station;
 pub mod station_snapshot;
 pub mod station_state;
\ No newline at end of file
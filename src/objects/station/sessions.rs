@@ -0,0 +1,233 @@
+// sessões dos ouvintes: uma linha JSONL por conexão encerrada, e os relatórios de horas ouvidas por dia
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::time::{Date, OffsetDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::objects::subscriber::Subscriber;
+
+pub const SESSION_LOG_FILE_NAME: &str = ".sessions.jsonl";
+
+/// Como a sessão acabou
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEnd {
+    /// o servidor encerrou o stream, ex.: ao desligar
+    Normal,
+    /// a conexão acabou do lado do ouvinte: ele fechou o player ou a rede caiu
+    Dropped,
+    /// derrubado pelo admin
+    Kicked,
+}
+
+impl SessionEnd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionEnd::Normal => "normal",
+            SessionEnd::Dropped => "dropped",
+            SessionEnd::Kicked => "kicked",
+        }
+    }
+}
+
+/// Uma conexão de ouvinte, do começo ao fim
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub station: String,
+    pub codec: String,
+    /// segundos Unix
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub bytes_sent: u64,
+    pub remote_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub listener_id: Option<String>,
+    pub end_reason: SessionEnd,
+}
+
+impl SessionRecord {
+    /// A sessão de `subscriber`, terminando agora
+    pub fn ended_now(station: &str, subscriber: &Subscriber, end_reason: SessionEnd) -> SessionRecord {
+        SessionRecord {
+            station: station.to_owned(),
+            codec: subscriber.codec.clone(),
+            started_at: unix_seconds(subscriber.connected_at),
+            ended_at: unix_seconds(SystemTime::now()),
+            bytes_sent: subscriber.bytes_sent.get() as u64,
+            remote_address: subscriber.remote_address,
+            user_agent: subscriber.user_agent.clone(),
+            listener_id: subscriber.listener_id.clone(),
+            end_reason,
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        (self.ended_at - self.started_at).max(0)
+    }
+
+    /// Quem ouviu: o ouvinte do token, se houver, senão o endereço. Sem nenhum dos dois, `None`
+    pub fn listener_key(&self) -> Option<String> {
+        self.listener_id
+            .as_ref()
+            .map(|listener| format!("listener:{}", listener))
+            .or_else(|| self.remote_address.map(|address| format!("address:{}", address)))
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Log JSONL das sessões de uma estação, só acrescentado
+pub struct SessionLog {
+    path: PathBuf,
+}
+
+impl SessionLog {
+    pub fn new(path: &Path) -> SessionLog {
+        SessionLog {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, record: &SessionRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| format!("sessions: falha ao serializar: {}", e))?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("sessions: falha ao gravar {}: {}", self.path.display(), e))
+    }
+
+    /// Sessões que começaram com `from <= started_at < to`; linhas corrompidas são ignoradas
+    pub fn range(&self, from: i64, to: i64) -> Result<Vec<SessionRecord>, String> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("sessions: falha ao abrir {}: {}", self.path.display(), e)),
+        };
+
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("sessions: falha ao ler {}: {}", self.path.display(), e))?;
+            if let Ok(record) = serde_json::from_str::<SessionRecord>(&line) {
+                if record.started_at >= from && record.started_at < to {
+                    records.push(record);
+                }
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+/// Números de um dia (UTC)
+#[derive(Clone, Debug, Serialize)]
+pub struct DailyListening {
+    /// `AAAA-MM-DD`
+    pub day: String,
+    pub sessions: usize,
+    pub listening_hours: f64,
+    pub average_session_seconds: f64,
+    /// ouvintes distintos pelo token ou, sem token, pelo endereço
+    pub unique_listeners: usize,
+}
+
+/// Agrupa as sessões pelo dia em que começaram, do mais antigo para o mais novo. Sessões de estações diferentes
+/// podem vir juntas: o mesmo ouvinte em duas estações conta uma vez só
+pub fn daily_report(records: &[SessionRecord]) -> Vec<DailyListening> {
+    #[derive(Default)]
+    struct Day {
+        sessions: usize,
+        seconds: i64,
+        listeners: BTreeSet<String>,
+        anonymous: usize,
+    }
+
+    let mut days: BTreeMap<Date, Day> = BTreeMap::new();
+    for record in records {
+        let Ok(started_at) = OffsetDateTime::from_unix_timestamp(record.started_at) else {
+            continue;
+        };
+        let day = days.entry(started_at.date()).or_default();
+        day.sessions += 1;
+        day.seconds += record.seconds();
+        match record.listener_key() {
+            Some(key) => {
+                day.listeners.insert(key);
+            }
+            // sem como identificar, cada sessão conta como um ouvinte
+            None => day.anonymous += 1,
+        }
+    }
+
+    days.into_iter()
+        .map(|(date, day)| DailyListening {
+            day: date.to_string(),
+            sessions: day.sessions,
+            listening_hours: day.seconds as f64 / 3600.0,
+            average_session_seconds: day.seconds as f64 / day.sessions as f64,
+            unique_listeners: day.listeners.len() + day.anonymous,
+        })
+        .collect()
+}
+
+/// CSV com cabeçalho, uma sessão por linha
+pub fn sessions_csv(records: &[SessionRecord]) -> String {
+    let mut csv = String::from(
+        "station,codec,started_at,ended_at,seconds,bytes_sent,remote_address,user_agent,listener_id,end_reason\n",
+    );
+    for record in records {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&record.station),
+            csv_field(&record.codec),
+            record.started_at,
+            record.ended_at,
+            record.seconds(),
+            record.bytes_sent,
+            record.remote_address.map(|address| address.to_string()).unwrap_or_default(),
+            csv_field(record.user_agent.as_deref().unwrap_or_default()),
+            csv_field(record.listener_id.as_deref().unwrap_or_default()),
+            record.end_reason.name()
+        );
+    }
+    csv
+}
+
+/// CSV com cabeçalho, um dia por linha
+pub fn report_csv(days: &[DailyListening]) -> String {
+    let mut csv = String::from("day,sessions,listening_hours,average_session_seconds,unique_listeners\n");
+    for day in days {
+        let _ = writeln!(
+            csv,
+            "{},{},{:.3},{:.1},{}",
+            day.day, day.sessions, day.listening_hours, day.average_session_seconds, day.unique_listeners
+        );
+    }
+    csv
+}
+
+/// Entre aspas quando precisa (RFC 4180); User-Agents costumam ter vírgulas
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
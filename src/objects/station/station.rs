@@ -1,8 +1,7 @@
-use std::{fs::File, path};
+use std::{fs::File, path, time::{SystemTime, UNIX_EPOCH}};
 
-use rocket::serde;
 
-use crate::objects::{station::station_state::StationState, subscriber::Subscriber, track::track::Track};
+use crate::objects::{station::{station_snapshot::StationSnapshot, station_state::StationState}, subscriber::Subscriber, track::track::Track};
 
 pub struct Station {
     pub name: String,
@@ -30,6 +29,18 @@ impl Station {
         station
     }
 
+    /// Cria a estação com faixas já carregadas (ex.: importadas de uma playlist), sem ler o metadata.json
+    pub fn with_tracks(name: String, path: String, frequency: f32, _state: Box<dyn StationState>, tracks: Vec<Track>) -> Station {
+        Station {
+            name,
+            _subscribers: Vec::new(),
+            path,
+            frequency,
+            _state,
+            tracks,
+        }
+    }
+
     pub fn add_subscriber(&mut self, subscriber: Subscriber) {
         self._subscribers.push(subscriber);
     }
@@ -42,6 +53,20 @@ impl Station {
         self._state = state;
     }
 
+    pub fn state_name(&self) -> &'static str {
+        self._state.name()
+    }
+
+    /// Cria o memento da estação para a faixa que acabou de entrar no ar
+    pub fn snapshot(&self, current_track: &Track, listeners: usize, created_at: SystemTime) -> StationSnapshot {
+        StationSnapshot {
+            name: self.name.clone(),
+            current_track: current_track.clone(),
+            listeners,
+            created_at: created_at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64),
+        }
+    }
+
     pub fn get_music_files(&self) -> Vec<String> {
         let mut music_vec = Vec::new();
         
@@ -60,17 +85,19 @@ impl Station {
         music_vec
     }
 
-    fn get_music_vec(&self) -> Vec<Track> {
-        let binding = self.path.clone() + "metadata.json";
-        let metadata_path = path::Path::new(&binding);
+    /// Lê as faixas do metadata.json de um diretório de estação
+    pub fn read_metadata(directory: &path::Path) -> Result<Vec<Track>, String> {
+        let metadata_path = directory.join("metadata.json");
 
-        let metadata_file: Vec<Track> = serde_json::from_reader(File::open(metadata_path).unwrap()).unwrap();
+        let metadata_file = File::open(&metadata_path)
+            .map_err(|e| format!("station: falha ao abrir {}: {}", metadata_path.display(), e))?;
 
-        metadata_file   
+        serde_json::from_reader(metadata_file)
+            .map_err(|e| format!("station: {} inválido: {}", metadata_path.display(), e))
     }
 
     fn fill_tracks(&mut self){
-        self.tracks = self.get_music_vec();
+        self.tracks = Self::read_metadata(path::Path::new(&self.path)).unwrap();
     }
 
     // Funções que acredito que vão estar no state
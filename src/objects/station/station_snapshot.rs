@@ -1,10 +1,25 @@
-use rocket::time::Date;
+use rocket::time::{Date, OffsetDateTime};
+use serde::{Deserialize, Serialize};
 
-use crate::objects::{subscriber::Subscriber, track::track::Track};
+use crate::objects::track::track::Track;
 
-struct StationSnapshot {
-    name: String,
-    current_track: Track,
-    subscribers: Vec<Subscriber>,
-    created_on: Date,
+/// Memento da estação no início de uma faixa: o que tocou, quando e para quantos ouvintes. Só a contagem: os
+/// dados de cada ouvinte (endereço, agente, token) não vão para o histórico em disco; as linhas antigas que os
+/// têm continuam sendo lidas
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StationSnapshot {
+    pub name: String,
+    pub current_track: Track,
+    pub listeners: usize,
+    /// segundos desde a época Unix (UTC)
+    pub created_at: i64,
+}
+
+impl StationSnapshot {
+    /// Dia (UTC) em que o snapshot foi criado
+    pub fn created_on(&self) -> Date {
+        OffsetDateTime::from_unix_timestamp(self.created_at)
+            .map(|datetime| datetime.date())
+            .unwrap_or(Date::MIN)
+    }
 }
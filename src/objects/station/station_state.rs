@@ -1,6 +1,7 @@
 // estrutura de dados para armazenar o estado da estação
-pub trait StationState {
-
+pub trait StationState: Send {
+    /// nome do estado, como aparece na API
+    fn name(&self) -> &'static str;
 }
 
 pub struct MockStationState {
@@ -13,4 +14,26 @@ impl MockStationState {
     }
 }
 
-impl StationState for MockStationState {}
\ No newline at end of file
+impl StationState for MockStationState {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+}
+
+/// Enchendo o buffer, antes da primeira faixa ou depois de um underrun
+pub struct Buffering;
+
+impl StationState for Buffering {
+    fn name(&self) -> &'static str {
+        "buffering"
+    }
+}
+
+/// Transmitindo normalmente
+pub struct OnAir;
+
+impl StationState for OnAir {
+    fn name(&self) -> &'static str {
+        "on_air"
+    }
+}
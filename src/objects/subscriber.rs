@@ -1,5 +1,74 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 // estrutura para armazenaro os clientes que estão escutando a estação
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Subscriber {
-    
+    /// mesmo id do cliente no `OutputStream`; único na rádio inteira
+    pub id: usize,
+    pub remote_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// ouvinte do token da URL assinada, se ele veio com um
+    pub listener_id: Option<String>,
+    /// nome do codec do stream, ex.: "mp3"
+    pub codec: String,
+    pub connected_at: SystemTime,
+    pub bytes_sent: ByteCounter,
+}
+
+impl Subscriber {
+    pub fn new(id: usize, remote_address: Option<IpAddr>, user_agent: Option<String>, codec: String) -> Subscriber {
+        Subscriber {
+            id,
+            remote_address,
+            user_agent,
+            listener_id: None,
+            codec,
+            connected_at: SystemTime::now(),
+            bytes_sent: ByteCounter::default(),
+        }
+    }
+}
+
+// o mesmo ouvinte, mesmo que o contador de bytes tenha andado
+impl PartialEq for Subscriber {
+    fn eq(&self, other: &Subscriber) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Contador de bytes compartilhado com o stream do cliente, que o atualiza a cada chunk enviado.
+/// Serializa como o valor no momento.
+#[derive(Clone, Debug, Default)]
+pub struct ByteCounter(Arc<AtomicUsize>);
+
+impl ByteCounter {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, bytes: usize) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Serialize for ByteCounter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.get() as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteCounter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteCounter, D::Error> {
+        let bytes = u64::deserialize(deserializer)?;
+        Ok(ByteCounter(Arc::new(AtomicUsize::new(bytes as usize))))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+// audiência de cada faixa: pico e média de ouvintes por execução, acumulados ao longo do tempo
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::objects::json_store::{load_json_or_default, save_json_atomically};
+
+pub const AUDIENCE_FILE_NAME: &str = ".audience.json";
+
+/// Acumulado de todas as execuções de uma faixa
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TrackAudience {
+    pub plays: u64,
+    /// maior pico entre todas as execuções
+    pub peak_listeners: usize,
+    /// pico da execução mais recente
+    pub last_peak_listeners: usize,
+    /// soma das médias de cada execução; dividida por `plays` dá a média geral
+    pub average_listeners_sum: f64,
+}
+
+impl TrackAudience {
+    pub fn average_listeners(&self) -> f64 {
+        if self.plays == 0 {
+            0.0
+        } else {
+            self.average_listeners_sum / self.plays as f64
+        }
+    }
+}
+
+/// Critério do ranking
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RankBy {
+    Peak,
+    Average,
+}
+
+pub struct AudienceBook {
+    path: PathBuf,
+    /// chave: `Track.source`
+    tracks: BTreeMap<String, TrackAudience>,
+}
+
+impl AudienceBook {
+    /// Carrega a audiência de `path`; se o arquivo não existir começa do zero
+    pub fn load(path: &Path) -> Result<AudienceBook, String> {
+        let tracks = load_json_or_default(path).map_err(|e| format!("audience: {}", e))?;
+
+        Ok(AudienceBook {
+            path: path.to_path_buf(),
+            tracks,
+        })
+    }
+
+    /// Soma uma execução completa da faixa e grava o arquivo
+    pub fn record_play(&mut self, source: &str, peak_listeners: usize, average_listeners: f64) -> Result<(), String> {
+        let audience = self.tracks.entry(source.to_owned()).or_default();
+        audience.plays += 1;
+        audience.peak_listeners = audience.peak_listeners.max(peak_listeners);
+        audience.last_peak_listeners = peak_listeners;
+        audience.average_listeners_sum += average_listeners;
+
+        self.save()
+    }
+
+    pub fn get(&self, source: &str) -> Option<&TrackAudience> {
+        self.tracks.get(source)
+    }
+
+    /// Faixas já tocadas, da maior para a menor audiência
+    pub fn ranking(&self, by: RankBy) -> Vec<(&str, &TrackAudience)> {
+        let mut ranking: Vec<(&str, &TrackAudience)> = self
+            .tracks
+            .iter()
+            .map(|(source, audience)| (source.as_str(), audience))
+            .collect();
+
+        match by {
+            RankBy::Peak => ranking.sort_by(|a, b| {
+                b.1.peak_listeners
+                    .cmp(&a.1.peak_listeners)
+                    .then(b.1.average_listeners().total_cmp(&a.1.average_listeners()))
+            }),
+            RankBy::Average => ranking.sort_by(|a, b| {
+                b.1.average_listeners()
+                    .total_cmp(&a.1.average_listeners())
+                    .then(b.1.peak_listeners.cmp(&a.1.peak_listeners))
+            }),
+        }
+
+        ranking
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_json_atomically(&self.path, &self.tracks).map_err(|e| format!("audience: {}", e))
+    }
+}
@@ -0,0 +1,201 @@
+// paradas das faixas: mais curtidas, mais tocadas, maior pico e mais horas de audiência, numa janela de tempo
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use rocket::time::{OffsetDateTime, Time};
+use serde::{Deserialize, Serialize};
+
+use crate::objects::station::likes::LikeBook;
+
+pub const PLAY_LOG_FILE_NAME: &str = ".plays.jsonl";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Uma execução completa de uma faixa, gravada quando ela sai do ar
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayRecord {
+    pub source: String,
+    /// segundos Unix
+    pub started_at: i64,
+    pub seconds: f64,
+    pub peak_listeners: usize,
+    pub average_listeners: f64,
+}
+
+/// Log JSONL das execuções de uma estação, só acrescentado
+pub struct PlayLog {
+    path: PathBuf,
+}
+
+impl PlayLog {
+    pub fn new(path: &Path) -> PlayLog {
+        PlayLog {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, record: &PlayRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| format!("charts: falha ao serializar: {}", e))?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("charts: falha ao gravar {}: {}", self.path.display(), e))
+    }
+
+    /// Execuções que começaram com `from <= started_at < to`; linhas corrompidas são ignoradas
+    pub fn range(&self, from: i64, to: i64) -> Result<Vec<PlayRecord>, String> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("charts: falha ao abrir {}: {}", self.path.display(), e)),
+        };
+
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("charts: falha ao ler {}: {}", self.path.display(), e))?;
+            if let Ok(record) = serde_json::from_str::<PlayRecord>(&line) {
+                if record.started_at >= from && record.started_at < to {
+                    records.push(record);
+                }
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChartWindow {
+    /// desde a meia-noite UTC
+    Today,
+    /// últimos 7 dias
+    Week,
+    AllTime,
+}
+
+impl ChartWindow {
+    pub fn from_name(name: &str) -> Option<ChartWindow> {
+        match name {
+            "today" => Some(ChartWindow::Today),
+            "7d" | "week" => Some(ChartWindow::Week),
+            "all" | "all-time" => Some(ChartWindow::AllTime),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartWindow::Today => "today",
+            ChartWindow::Week => "7d",
+            ChartWindow::AllTime => "all",
+        }
+    }
+
+    /// Intervalo `[from, to)` em segundos Unix
+    pub fn bounds(&self, now: OffsetDateTime) -> (i64, i64) {
+        let to = now.unix_timestamp() + 1;
+        match self {
+            ChartWindow::Today => (now.date().with_time(Time::MIDNIGHT).assume_utc().unix_timestamp(), to),
+            ChartWindow::Week => (to - 7 * SECONDS_PER_DAY, to),
+            ChartWindow::AllTime => (i64::MIN, i64::MAX),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChartBy {
+    Likes,
+    Plays,
+    Peak,
+    ListenerHours,
+}
+
+impl ChartBy {
+    pub fn from_name(name: &str) -> Option<ChartBy> {
+        match name {
+            "likes" => Some(ChartBy::Likes),
+            "plays" => Some(ChartBy::Plays),
+            "peak" => Some(ChartBy::Peak),
+            "listener_hours" | "hours" => Some(ChartBy::ListenerHours),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartBy::Likes => "likes",
+            ChartBy::Plays => "plays",
+            ChartBy::Peak => "peak",
+            ChartBy::ListenerHours => "listener_hours",
+        }
+    }
+}
+
+/// Números de uma faixa dentro da janela
+#[derive(Clone, Default)]
+pub struct ChartEntry {
+    pub station: String,
+    pub source: String,
+    pub likes: usize,
+    pub plays: u64,
+    pub peak_listeners: usize,
+    pub listener_hours: f64,
+}
+
+/// Junta as execuções e os likes de uma estação por faixa. Faixas sem execuções nem likes na janela ficam de fora.
+/// A ordem não é definida; use `sort_chart`, que também serve para juntar estações diferentes
+pub fn station_chart(station: &str, plays: &[PlayRecord], likes: &LikeBook, from: i64, to: i64) -> Vec<ChartEntry> {
+    let mut entries: BTreeMap<&str, ChartEntry> = BTreeMap::new();
+
+    for play in plays.iter().filter(|play| play.started_at >= from && play.started_at < to) {
+        let entry = entries.entry(&play.source).or_default();
+        entry.plays += 1;
+        entry.peak_listeners = entry.peak_listeners.max(play.peak_listeners);
+        entry.listener_hours += play.average_listeners * play.seconds / 3600.0;
+    }
+
+    for (source, count) in likes.track_likes_between(from, to) {
+        entries.entry(source).or_default().likes = count;
+    }
+
+    entries
+        .into_iter()
+        .map(|(source, mut entry)| {
+            entry.station = station.to_owned();
+            entry.source = source.to_owned();
+            entry
+        })
+        .collect()
+}
+
+/// Ordena do maior para o menor pelo critério; empates são decididos pelos outros critérios e, por fim, pelo nome
+pub fn sort_chart(entries: &mut [ChartEntry], by: ChartBy) {
+    fn compare(a: &ChartEntry, b: &ChartEntry, by: ChartBy) -> Ordering {
+        match by {
+            ChartBy::Likes => a.likes.cmp(&b.likes),
+            ChartBy::Plays => a.plays.cmp(&b.plays),
+            ChartBy::Peak => a.peak_listeners.cmp(&b.peak_listeners),
+            ChartBy::ListenerHours => a.listener_hours.total_cmp(&b.listener_hours),
+        }
+    }
+
+    entries.sort_by(|a, b| {
+        [by, ChartBy::Likes, ChartBy::Plays, ChartBy::Peak, ChartBy::ListenerHours]
+            .iter()
+            .map(|by| compare(b, a, *by))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| (&a.station, &a.source).cmp(&(&b.station, &b.source)))
+    });
+}
@@ -1,2 +1,5 @@
+pub mod audience;
+pub mod charts;
+pub mod playlist;
 pub mod track;
 pub mod track_iterator;
\ No newline at end of file
@@ -0,0 +1,347 @@
+// importação de playlists em formatos padrão (M3U/M3U8, PLS e XSPF), como alternativa ao metadata.json
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use super::track::Track;
+
+/// Uma entrada da playlist que não pôde ser usada
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingEntry {
+    /// Posição da entrada na playlist, começando em 1
+    pub position: usize,
+    /// Como a entrada foi escrita na playlist
+    pub entry: String,
+    /// Caminho resolvido, se a entrada era um arquivo local
+    pub resolved: Option<PathBuf>,
+}
+
+/// Resultado da importação de uma playlist: as faixas encontradas e as entradas que ficaram de fora
+#[derive(Clone, Default)]
+pub struct PlaylistImport {
+    pub tracks: Vec<Track>,
+    pub missing: Vec<MissingEntry>,
+}
+
+impl fmt::Display for PlaylistImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} faixas importadas, {} entradas faltando",
+            self.tracks.len(),
+            self.missing.len()
+        )?;
+
+        for missing in &self.missing {
+            match &missing.resolved {
+                Some(resolved) => write!(
+                    f,
+                    "\n  #{}: arquivo não encontrado: {}",
+                    missing.position,
+                    resolved.display()
+                )?,
+                None => write!(
+                    f,
+                    "\n  #{}: entrada não suportada: {}",
+                    missing.position, missing.entry
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Uma entrada lida da playlist, antes de resolvermos o caminho
+#[derive(Default)]
+struct PlaylistEntry {
+    location: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<u32>, // em segundos
+}
+
+/// Importa uma playlist, escolhendo o formato pela extensão do arquivo.
+/// Caminhos relativos são resolvidos a partir do diretório da playlist.
+pub fn import(playlist_path: &Path) -> Result<PlaylistImport, String> {
+    let content = fs::read_to_string(playlist_path).map_err(|e| {
+        format!(
+            "playlist: falha ao ler {}: {}",
+            playlist_path.display(),
+            e
+        )
+    })?;
+
+    let extension = playlist_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+
+    let entries = match extension.as_deref() {
+        Some("m3u") | Some("m3u8") => parse_m3u(&content),
+        Some("pls") => parse_pls(&content)?,
+        Some("xspf") => parse_xspf(&content)?,
+        _ => {
+            return Err(format!(
+                "playlist: formato de playlist desconhecido: {}",
+                playlist_path.display()
+            ))
+        }
+    };
+
+    let base_directory = playlist_path.parent().unwrap_or(Path::new("."));
+    Ok(resolve_entries(entries, base_directory))
+}
+
+fn resolve_entries(entries: Vec<PlaylistEntry>, base_directory: &Path) -> PlaylistImport {
+    let mut import = PlaylistImport::default();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let position = index + 1;
+
+        let local_path = match local_path(&entry.location) {
+            Some(path) => base_directory.join(path),
+            None => {
+                import.missing.push(MissingEntry {
+                    position,
+                    entry: entry.location,
+                    resolved: None,
+                });
+                continue;
+            }
+        };
+
+        if !local_path.is_file() {
+            import.missing.push(MissingEntry {
+                position,
+                entry: entry.location,
+                resolved: Some(local_path),
+            });
+            continue;
+        }
+
+        let title = entry.title.unwrap_or_else(|| {
+            local_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        let file_format = local_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        import.tracks.push(Track::new(
+            title,
+            entry.artist.unwrap_or_default(),
+            entry.album.unwrap_or_default(),
+            entry.duration.unwrap_or(0),
+            file_format,
+            local_path.to_string_lossy().into_owned(),
+            Vec::new(),
+            Vec::new(),
+        ));
+    }
+
+    import
+}
+
+/// Converte a localização de uma entrada para um caminho local; URLs remotas não são suportadas
+fn local_path(location: &str) -> Option<PathBuf> {
+    if let Some(path) = location.strip_prefix("file://") {
+        // file:///home/... ou file://localhost/home/...
+        let path = path.strip_prefix("localhost").unwrap_or(path);
+        return Some(PathBuf::from(percent_decode(path)));
+    }
+
+    if location.contains("://") {
+        return None;
+    }
+
+    // playlists geradas no Windows usam barra invertida
+    Some(PathBuf::from(location.replace('\\', "/")))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Separa "Artista - Título", o formato mais comum nos `#EXTINF` e títulos de PLS
+fn split_artist_title(display: &str) -> (Option<String>, String) {
+    match display.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_owned()), title.trim().to_owned()),
+        None => (None, display.trim().to_owned()),
+    }
+}
+
+/// Durações negativas (-1) significam "desconhecida" tanto no M3U quanto no PLS
+fn parse_duration_seconds(value: &str) -> Option<u32> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .map(|seconds| seconds.round() as u32)
+}
+
+fn parse_m3u(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    // M3U8 é UTF-8 e pode vir com BOM
+    for line in content.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<duração>[ atributos],<Artista - Título>
+            let (head, display) = info.split_once(',').unwrap_or((info, ""));
+            let duration = head.split_whitespace().next().unwrap_or("");
+            pending.duration = parse_duration_seconds(duration);
+
+            if !display.trim().is_empty() {
+                let (artist, title) = split_artist_title(display);
+                pending.artist = artist;
+                pending.title = Some(title);
+            }
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            pending.album = Some(album.trim().to_owned());
+        } else if line.is_empty() || line.starts_with('#') {
+            // comentário ou diretiva que não usamos
+        } else {
+            pending.location = line.to_owned();
+            entries.push(std::mem::take(&mut pending));
+        }
+    }
+
+    entries
+}
+
+fn parse_pls(content: &str) -> Result<Vec<PlaylistEntry>, String> {
+    // as chaves do PLS são numeradas (File1, Title1, Length1...) e podem vir fora de ordem
+    let mut entries: Vec<(usize, PlaylistEntry)> = Vec::new();
+    let mut seen_header = false;
+
+    for line in content.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("[playlist]") {
+            seen_header = true;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        let (field, number) = match key.find(|c: char| c.is_ascii_digit()) {
+            Some(split) => (&key[..split], &key[split..]),
+            None => continue, // NumberOfEntries, Version...
+        };
+        let Ok(number) = number.parse::<usize>() else {
+            continue;
+        };
+
+        let entry = match entries.iter_mut().find(|(n, _)| *n == number) {
+            Some((_, entry)) => entry,
+            None => {
+                entries.push((number, PlaylistEntry::default()));
+                &mut entries.last_mut().unwrap().1
+            }
+        };
+
+        match field {
+            "file" => entry.location = value.to_owned(),
+            "title" => {
+                let (artist, title) = split_artist_title(value);
+                entry.artist = artist;
+                entry.title = Some(title);
+            }
+            "length" => entry.duration = parse_duration_seconds(value),
+            _ => {}
+        }
+    }
+
+    if !seen_header {
+        return Err("playlist: arquivo PLS sem a seção [playlist]".to_owned());
+    }
+
+    entries.sort_by_key(|(number, _)| *number);
+    Ok(entries
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .filter(|entry| !entry.location.is_empty())
+        .collect())
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let track_list = xml_element(content, "trackList")
+        .ok_or("playlist: arquivo XSPF sem <trackList>")?;
+
+    let mut entries = Vec::new();
+    let mut rest = track_list;
+
+    while let Some(start) = rest.find("<track>") {
+        let after_start = &rest[start + "<track>".len()..];
+        let end = after_start
+            .find("</track>")
+            .ok_or("playlist: <track> sem fechamento no XSPF")?;
+        let track = &after_start[..end];
+        rest = &after_start[end + "</track>".len()..];
+
+        let Some(location) = xml_element(track, "location") else {
+            continue;
+        };
+
+        entries.push(PlaylistEntry {
+            location: xml_unescape(location.trim()),
+            title: xml_element(track, "title").map(|t| xml_unescape(t.trim())),
+            artist: xml_element(track, "creator").map(|t| xml_unescape(t.trim())),
+            album: xml_element(track, "album").map(|t| xml_unescape(t.trim())),
+            // no XSPF a duração é em milissegundos
+            duration: xml_element(track, "duration")
+                .and_then(|d| d.trim().parse::<u64>().ok())
+                .map(|ms| ((ms + 500) / 1000) as u32),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Conteúdo do primeiro elemento `<tag>...</tag>` (sem atributos) dentro de `xml`
+fn xml_element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(&xml[start..end])
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
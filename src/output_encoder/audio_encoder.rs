@@ -1,12 +1,14 @@
 use bytes::Bytes;
 use std::{
     io::{BufReader, BufWriter, Read, Write},
-    process::{ChildStdin, Command, Stdio},
-    sync::Arc,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{atomic::Ordering, Arc},
     thread,
 };
 
-use crate::{input_decoder::input_audio_file::AudioPacket, output_stream::OutputStream};
+use tracing::{debug, error, info_span, warn};
+
+use crate::{input_decoder::input_audio_file::AudioPacket, output_stream::MountCounters};
 
 pub const INPUT_CHANNEL_COUNT: u32 = 2;
 pub const INPUT_SAMPLE_RATE: u32 = 44100;
@@ -18,32 +20,77 @@ pub const OUTPUT_BYTE_DEPTH: u32 = 2; //16bits
 
 pub const MAX_STATION_LISTENERS: usize = 64; // quantos players podem ouvir essa estação de uma vez?
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum OutputCodec {
     Mp3_64kbps,
     Ogg96kbps,
     Opus128kbps,
+    /// AAC-LC em ADTS
+    Aac128kbps,
+    /// HE-AAC (AAC-LC + SBR) em ADTS, para conexões móveis fracas
+    HeAac48kbps,
+    /// HE-AACv2 (SBR + estéreo paramétrico) em ADTS, para os bitrates mais baixos
+    HeAacV2_32kbps,
+    /// FLAC sem perdas no container nativo; o bitrate varia com a música
+    Flac,
 }
 
-fn ffmpeg_args(output_codec: &OutputCodec) -> Vec<String> {
-    let sample_rate = INPUT_SAMPLE_RATE.to_string();
-    let channel_count = INPUT_CHANNEL_COUNT.to_string();
-
-    let mut args = vec![
-        "-f",
-        "s16le",
-        "-ar",
-        &sample_rate,
-        "-ac",
-        &channel_count,
-        "-i",
-        "-", // stdin como input pro ffmpeg
+impl OutputCodec {
+    /// Todos os codecs de saída suportados
+    pub const ALL: [OutputCodec; 7] = [
+        OutputCodec::Mp3_64kbps,
+        OutputCodec::Ogg96kbps,
+        OutputCodec::Opus128kbps,
+        OutputCodec::Aac128kbps,
+        OutputCodec::HeAac48kbps,
+        OutputCodec::HeAacV2_32kbps,
+        OutputCodec::Flac,
     ];
 
-    args.append(&mut match output_codec {
+    /// Nome curto do codec, usado no arquivo de configuração
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputCodec::Mp3_64kbps => "mp3",
+            OutputCodec::Ogg96kbps => "ogg",
+            OutputCodec::Opus128kbps => "opus",
+            OutputCodec::Aac128kbps => "aac",
+            OutputCodec::HeAac48kbps => "he-aac",
+            OutputCodec::HeAacV2_32kbps => "he-aacv2",
+            OutputCodec::Flac => "flac",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputCodec> {
+        Self::ALL
+            .iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Bitrate usado quando a configuração não especifica um; `None` nos codecs sem perdas, que não têm bitrate
+    pub fn default_bitrate_kbps(&self) -> Option<u32> {
+        match self {
+            OutputCodec::Mp3_64kbps => Some(128),
+            OutputCodec::Ogg96kbps => Some(96),
+            OutputCodec::Opus128kbps => Some(128),
+            OutputCodec::Aac128kbps => Some(128),
+            OutputCodec::HeAac48kbps => Some(48),
+            OutputCodec::HeAacV2_32kbps => Some(32),
+            OutputCodec::Flac => None,
+        }
+    }
+}
+
+/// Argumentos do ffmpeg para um codec de saída dos streams HTTP.
+/// O encoder AAC nativo do ffmpeg só faz AAC-LC; os perfis HE-AAC precisam de um ffmpeg compilado com libfdk_aac.
+/// `bitrate_kbps` só é `None` nos codecs sem perdas
+pub fn ffmpeg_args(output_codec: &OutputCodec, bitrate_kbps: Option<u32>) -> Vec<String> {
+    let bitrate = bitrate_kbps.map(|kbps| format!("{}k", kbps)).unwrap_or_default();
+
+    ffmpeg_command(&match output_codec {
         OutputCodec::Mp3_64kbps => vec![
             "-b:a",
-            "128k",
+            &bitrate,
             "-f",
             "mp3",
             "-flush_packets",
@@ -53,10 +100,54 @@ fn ffmpeg_args(output_codec: &OutputCodec) -> Vec<String> {
             "-id3v2_version",
             "0",
         ],
-        OutputCodec::Ogg96kbps => vec!["-b:a", "96k", "-f", "ogg"],
-        OutputCodec::Opus128kbps => vec!["-c:a", "libopus", "-b:a", "128k", "-f", "opus"],
-    });
+        OutputCodec::Ogg96kbps => vec!["-b:a", &bitrate, "-f", "ogg"],
+        OutputCodec::Opus128kbps => vec!["-c:a", "libopus", "-b:a", &bitrate, "-f", "opus"],
+        OutputCodec::Aac128kbps => vec!["-c:a", "aac", "-b:a", &bitrate, "-f", "adts", "-flush_packets", "1"],
+        OutputCodec::HeAac48kbps => vec![
+            "-c:a",
+            "libfdk_aac",
+            "-profile:a",
+            "aac_he",
+            "-b:a",
+            &bitrate,
+            "-f",
+            "adts",
+            "-flush_packets",
+            "1",
+        ],
+        OutputCodec::HeAacV2_32kbps => vec![
+            "-c:a",
+            "libfdk_aac",
+            "-profile:a",
+            "aac_he_v2",
+            "-b:a",
+            &bitrate,
+            "-f",
+            "adts",
+            "-flush_packets",
+            "1",
+        ],
+        OutputCodec::Flac => vec!["-c:a", "flac", "-f", "flac", "-flush_packets", "1"],
+    })
+}
+
+/// Linha de comando completa do ffmpeg: PCM do stdin, os argumentos de saída e o resultado no stdout
+pub fn ffmpeg_command(output_args: &[&str]) -> Vec<String> {
+    let sample_rate = INPUT_SAMPLE_RATE.to_string();
+    let channel_count = INPUT_CHANNEL_COUNT.to_string();
 
+    let mut args = vec![
+        "-f",
+        "s16le",
+        "-ar",
+        &sample_rate,
+        "-ac",
+        &channel_count,
+        "-i",
+        "-", // stdin como input pro ffmpeg
+    ];
+
+    args.extend_from_slice(output_args);
     args.push("-"); // stdout como output pro ffmpeg
 
     return args.iter().map(|f| f.to_string()).collect();
@@ -64,17 +155,39 @@ fn ffmpeg_args(output_codec: &OutputCodec) -> Vec<String> {
 
 pub type ConsumerPacket = Bytes;
 
+/// Quem recebe o áudio codificado que sai do ffmpeg: um stream HTTP (`OutputStream`) ou o segmentador do HLS
+pub trait EncodedOutput: Send + Sync {
+    fn push(&self, packet: Bytes);
+    /// nome do mount, para os logs
+    fn mount(&self) -> &str;
+    fn counters(&self) -> &MountCounters;
+    /// O ffmpeg foi reiniciado: o que chegou pela metade do anterior não vai ser completado
+    fn restarted(&self) {}
+}
+
 // singleton - um por estação
 pub struct AudioEncoder {
+    args: Vec<String>,
+    output: Arc<dyn EncodedOutput>,
     encoder_in: BufWriter<ChildStdin>,
-    child: std::process::Child,
+    child: Child,
 }
 
 impl AudioEncoder {
-    pub fn new(output_codec: &OutputCodec, output: Arc<OutputStream>) -> AudioEncoder {
-        let args: Vec<String> = ffmpeg_args(&output_codec);
+    /// `args` vem de `ffmpeg_args` ou `ffmpeg_command`
+    pub fn new(args: Vec<String>, output: Arc<dyn EncodedOutput>) -> AudioEncoder {
+        let (child, encoder_in) = Self::spawn_ffmpeg(&args, output.clone());
+
+        AudioEncoder {
+            args,
+            output,
+            encoder_in,
+            child,
+        }
+    }
 
-        println!("encoder: Parâmetros ffmpeg: {:?}", args);
+    fn spawn_ffmpeg(args: &[String], output: Arc<dyn EncodedOutput>) -> (Child, BufWriter<ChildStdin>) {
+        debug!(mount = output.mount(), ?args, "iniciando ffmpeg");
 
         let mut child = Command::new("ffmpeg")
             .args(args)
@@ -86,44 +199,64 @@ impl AudioEncoder {
 
         if let Some(stdout) = child.stdout.take() {
             let mut stdout_reader = BufReader::new(stdout);
+            let span = info_span!("encoder", mount = output.mount());
             thread::spawn(move || {
-                println!("encoder: Thread de consumidor de áudio iniciada.");
+                let _span = span.entered();
+                debug!("thread de consumidor de áudio iniciada");
 
                 let mut buf = vec![0u8; 8192];
                 loop {
-                    let n = stdout_reader
-                        .read(&mut buf)
-                        .expect("encoder: Ler stdout do encoder falhou - processo crashou?");
-
-                    match n {
-                        0 => panic!("encoder: Stdout finalizou, estação acabou!"),
-                        1.. => {
-                            // não é exatamente zero-copy, mas sim "one-copy"
-                            // uma vez que alocamos esse Bytes, ele é reference-counted, igual o Arc
-                            // ao transmití-lo pelo tokio::sync::broadcast::Sender ele não vai fazer novas cópias de memória
-                            // então pagamos um custo fixo, uma vez só
-                            let packet = Bytes::copy_from_slice(&buf[..n]);
-
-                            output.push(packet);
+                    // stdout fechado: o ffmpeg morreu, e quem reinicia é o push_audio_packet, com uma thread nova
+                    let n = match stdout_reader.read(&mut buf) {
+                        Ok(0) => {
+                            warn!("stdout do ffmpeg finalizou");
+                            return;
+                        }
+                        Ok(n) => n,
+                        Err(e) => {
+                            error!("ler stdout do ffmpeg falhou: {}", e);
+                            return;
                         }
-                    }
+                    };
+
+                    // não é exatamente zero-copy, mas sim "one-copy"
+                    // uma vez que alocamos esse Bytes, ele é reference-counted, igual o Arc
+                    // ao transmití-lo pelo tokio::sync::broadcast::Sender ele não vai fazer novas cópias de memória
+                    // então pagamos um custo fixo, uma vez só
+                    let packet = Bytes::copy_from_slice(&buf[..n]);
+                    output.counters().encoded.record(n);
+
+                    output.push(packet);
                 }
             });
         }
 
         let stdin = child.stdin.take().expect("encoder: Falha ao ler stdin");
-        let stdin_writer = BufWriter::new(stdin);
+        (child, BufWriter::new(stdin))
+    }
 
-        AudioEncoder {
-            encoder_in: stdin_writer,
-            child: child,
-        }
+    /// O ffmpeg morreu: sobe outro com os mesmos parâmetros. Os ouvintes continuam conectados,
+    /// só perdem o áudio do intervalo
+    fn restart(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+
+        let (child, encoder_in) = Self::spawn_ffmpeg(&self.args, self.output.clone());
+        self.child = child;
+        self.encoder_in = encoder_in;
+        self.output.restarted();
+        self.output
+            .counters()
+            .encoder_restarts
+            .fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn push_audio_packet(&mut self, packet: AudioPacket) {
-        self.encoder_in
-            .write(&packet.buffer)
-            .expect("encoder: A fila do ffmpeg está cheia?");
+        if let Err(e) = self.encoder_in.write_all(&packet.buffer) {
+            warn!(mount = self.output.mount(), "ffmpeg não aceita mais áudio: {} - reiniciando", e);
+            self.restart();
+            return;
+        }
 
         // bypass do buffer do stdin; manda direto pro ffmpeg, já que áudio é em real-time e talvez não seja legal ter esse comportamento de buffering
         // ignoramos o Result propositalmente, não há nenhuma ação cabível a ser tomada se o buffer de stdin não pode ser flushado - meio que não importa
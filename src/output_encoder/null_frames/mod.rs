@@ -20,11 +20,23 @@ use super::audio_encoder::OutputCodec;
 /// `ffmpeg -f lavfi -i anullsrc=channel_layout=stereo:sample_rate=44100 -acodec libmp3lame -ab 128k -ac 2 -ar 44100 -t 0.02 -vn -sn -f mp3 - | head -c 1472 > mp3_null_frame.bin`
 /// `ffmpeg -f lavfi -i anullsrc=channel_layout=stereo:sample_rate=48000 -acodec libopus -ab 128k -ac 2 -ar 48000 -t 0.02 -vn -sn -f opus - > opus_null_frame.opus`
 /// `ffmpeg -f lavfi -i anullsrc=channel_layout=stereo:sample_rate=44100 -acodec libvorbis -ab 128k -ac 2 -ar 44100 -t 0.02 -vn -sn -f ogg - > ogg_vorbis_null_frame.ogg`
-pub fn get_null_frame(codec: &OutputCodec) -> &'static [u8] {
+///
+/// Os de AAC foram montados à mão: um cabeçalho ADTS e um bloco com um elemento sem espectro (`max_sfb` 0) e `ID_END`.
+/// `aac.bin` é estéreo (CPE) a 44100 Hz; nos HE-AAC o ADTS descreve o núcleo AAC-LC, a 22050 Hz, e o SBR fica
+/// implícito, como no que o libfdk_aac gera: estéreo no `he-aac.bin` e mono (SCE) no `he-aacv2.bin`, já que o
+/// estéreo paramétrico também é implícito
+///
+/// O FLAC não tem frame null: o decoder precisa do cabeçalho `fLaC` com o STREAMINFO do próprio encoder, que o
+/// mount guarda e manda no lugar (ver `output_stream::flac`)
+pub fn get_null_frame(codec: &OutputCodec) -> Option<&'static [u8]> {
     match codec {
-        OutputCodec::Mp3_64kbps => include_bytes!("./mp3.bin"),
-        OutputCodec::Ogg96kbps => include_bytes!("./ogg.bin"),
-        OutputCodec::Opus128kbps => include_bytes!("./opus.bin"),
+        OutputCodec::Mp3_64kbps => Some(include_bytes!("./mp3.bin")),
+        OutputCodec::Ogg96kbps => Some(include_bytes!("./ogg.bin")),
+        OutputCodec::Opus128kbps => Some(include_bytes!("./opus.bin")),
+        OutputCodec::Aac128kbps => Some(include_bytes!("./aac.bin")),
+        OutputCodec::HeAac48kbps => Some(include_bytes!("./he-aac.bin")),
+        OutputCodec::HeAacV2_32kbps => Some(include_bytes!("./he-aacv2.bin")),
+        OutputCodec::Flac => None,
     }
 }
 
@@ -33,5 +45,7 @@ pub fn get_mime_type(codec: &OutputCodec) -> &'static str {
         OutputCodec::Mp3_64kbps => "mpeg",
         OutputCodec::Ogg96kbps => "ogg",
         OutputCodec::Opus128kbps => "opus",
+        OutputCodec::Aac128kbps | OutputCodec::HeAac48kbps | OutputCodec::HeAacV2_32kbps => "aac",
+        OutputCodec::Flac => "flac",
     }
 }
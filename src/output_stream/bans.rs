@@ -0,0 +1,79 @@
+// banimentos temporários por endereço, aplicados pelo admin ao derrubar um ouvinte
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+
+use tracing::warn;
+
+use super::client::client_address;
+
+/// banimento mais longo que se pode aplicar, pelo admin ou pelo controle de flood: um ano
+pub const MAX_BAN_MINUTES: u64 = 365 * 24 * 60;
+
+#[derive(Default)]
+pub struct BanList {
+    bans: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl BanList {
+    pub fn ban(&self, address: IpAddr, duration: Duration) {
+        match Instant::now().checked_add(duration) {
+            Some(until) => {
+                self.bans.lock().unwrap().insert(address, until);
+            }
+            None => warn!(%address, seconds = duration.as_secs(), "banimento longo demais, ignorado"),
+        }
+    }
+
+    /// Bans vencidos são descartados aqui mesmo
+    pub fn is_banned(&self, address: &IpAddr) -> bool {
+        let mut bans = self.bans.lock().unwrap();
+        match bans.get(address) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                bans.remove(address);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Bans ativos, com o tempo restante
+    pub fn list(&self) -> Vec<(IpAddr, Duration)> {
+        let now = Instant::now();
+        let mut bans = self.bans.lock().unwrap();
+        bans.retain(|_, until| *until > now);
+
+        bans.iter().map(|(address, until)| (*address, *until - now)).collect()
+    }
+}
+
+/// Guard dos endpoints de stream: responde 403 para endereços banidos
+pub struct NotBanned;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for NotBanned {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, String> {
+        let banned = match (request.rocket().state::<BanList>(), client_address(request)) {
+            (Some(bans), Some(address)) => bans.is_banned(&address),
+            _ => false,
+        };
+
+        if banned {
+            Outcome::Error((Status::Forbidden, "endereço banido temporariamente".to_owned()))
+        } else {
+            Outcome::Success(NotBanned)
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// bitrate medido do que sai do ffmpeg; os codecs VBR, como o FLAC, não têm um bitrate nominal para reportar
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use web_radio::objects::subscriber::ByteCounter;
+
+/// Janela da média: longa o bastante para atravessar as rajadas de PCM que chegam aos encoders
+const WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct Window {
+    started: Option<Instant>,
+    samples: VecDeque<(Instant, usize)>,
+}
+
+/// Bytes que um encoder produziu: o total desde que o servidor subiu e a taxa dos últimos `WINDOW`
+#[derive(Default)]
+pub struct BitrateMeter {
+    total: ByteCounter,
+    window: Mutex<Window>,
+}
+
+impl BitrateMeter {
+    pub fn record(&self, bytes: usize) {
+        self.total.add(bytes);
+
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        window.started.get_or_insert(now);
+        window.samples.push_back((now, bytes));
+        while window
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+        {
+            window.samples.pop_front();
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.get()
+    }
+
+    /// Bits por segundo nos últimos `WINDOW` (ou desde o primeiro byte, se faz menos tempo); 0 sem encoder rodando
+    pub fn bits_per_second(&self) -> f64 {
+        let now = Instant::now();
+        let window = self.window.lock().unwrap();
+        let Some(started) = window.started else {
+            return 0.0;
+        };
+
+        let elapsed = now.duration_since(started).min(WINDOW).as_secs_f64();
+        let bytes: usize = window
+            .samples
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= WINDOW)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        if elapsed > 0.0 {
+            (bytes as f64 * 8.0) / elapsed
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// limites de ouvintes por estação e por codec, com vagas reservadas para usuários autenticados
+
+/// Limites de um mount. `station_max` e `reserved_slots` são da estação inteira (todos os codecs somados)
+#[derive(Clone, Copy, Debug)]
+pub struct ListenerLimits {
+    pub station_max: usize,
+    pub codec_max: usize,
+    /// vagas da estação que só usuários autenticados podem ocupar
+    pub reserved_slots: usize,
+}
+
+impl ListenerLimits {
+    /// `station_listeners` e `codec_listeners` são os ouvintes já conectados, sem contar o novo
+    pub fn admits(&self, station_listeners: usize, codec_listeners: usize, privileged: bool) -> bool {
+        let station_max = if privileged {
+            self.station_max
+        } else {
+            self.station_max.saturating_sub(self.reserved_slots)
+        };
+
+        station_listeners < station_max && codec_listeners < self.codec_max
+    }
+}
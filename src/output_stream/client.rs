@@ -0,0 +1,52 @@
+// quem está pedindo o stream: endereço e User-Agent, guardados no Subscriber,
+// se ele pode usar as vagas reservadas da estação e o token da URL assinada, se veio um
+
+use std::net::IpAddr;
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::{api::admin::AdminAuth, config::ProxyConfig};
+
+/// Endereço de quem fez o pedido: o da conexão ou, se ela vem de um proxy listado em `proxy.trusted`, o do header
+/// configurado. Headers de qualquer outra origem são ignorados, senão cada pedido poderia dizer um endereço novo e
+/// escapar dos limites por endereço e dos banimentos
+pub fn client_address(request: &Request<'_>) -> Option<IpAddr> {
+    let peer = request.remote()?.ip().to_canonical();
+    let Some(proxy) = request
+        .rocket()
+        .state::<ProxyConfig>()
+        .filter(|proxy| proxy.trusted.contains(&peer))
+    else {
+        return Some(peer);
+    };
+
+    let forwarded = request
+        .headers()
+        .get_one(&proxy.header)
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|address| address.trim().parse::<IpAddr>().ok());
+    Some(forwarded.unwrap_or(peer))
+}
+
+pub struct ClientIdentity {
+    pub remote_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// autenticado com o token de admin
+    pub privileged: bool,
+    /// `?token=` da URL; conferido pelo mount, que sabe a estação
+    pub token: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientIdentity {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(ClientIdentity {
+            remote_address: client_address(request),
+            user_agent: request.headers().get_one("User-Agent").map(str::to_owned),
+            privileged: request.guard::<AdminAuth>().await.succeeded().is_some(),
+            token: request.query_value::<String>("token").and_then(Result::ok),
+        })
+    }
+}
@@ -0,0 +1,548 @@
+// MPEG-DASH: um ffmpeg só da estação gera MP4 fragmentado (AAC ou Opus) a partir do mesmo PCM dos outros encoders.
+// A saída é separada em caixas: `ftyp` + `moov` viram o segmento de inicialização, e cada `moof` + `mdat`,
+// um segmento de mídia. O manifesto (MPD) é dinâmico, com uma SegmentTimeline dos últimos segmentos
+
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use rocket::{
+    http::{ContentType, Header},
+    time::OffsetDateTime,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::DashConfig,
+    output_encoder::audio_encoder::{ffmpeg_command, EncodedOutput},
+};
+
+use super::{token::StreamAccess, MountCounters, SegmentRefused, SegmentResponse};
+
+/// segmentos que já saíram do manifesto mas ainda podem ser baixados
+const SEGMENTS_KEPT_AFTER_WINDOW: usize = 2;
+/// o manifesto só é servido com pelo menos esses segmentos
+const MIN_SEGMENTS_TO_START: usize = 3;
+/// maior caixa de topo aceita: um fragmento tem poucos segundos de áudio (dezenas de KiB); mais que isso é um
+/// tamanho corrompido, que deixaria o `pending` crescendo para sempre à espera do fim da caixa
+const MAX_BOX_BYTES: usize = 16 * 1024 * 1024;
+/// caixas de topo que o ffmpeg pode mandar e que não usamos
+const IGNORED_BOXES: [&[u8; 4]; 5] = [b"free", b"skip", b"styp", b"sidx", b"mfra"];
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum DashCodec {
+    Aac,
+    Opus,
+}
+
+impl DashCodec {
+    pub const ALL: [DashCodec; 2] = [DashCodec::Aac, DashCodec::Opus];
+
+    /// Nome curto do codec, usado no arquivo de configuração
+    pub fn name(&self) -> &'static str {
+        match self {
+            DashCodec::Aac => "aac",
+            DashCodec::Opus => "opus",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DashCodec> {
+        Self::ALL
+            .iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    pub fn default_bitrate_kbps(&self) -> u32 {
+        match self {
+            DashCodec::Aac => 128,
+            DashCodec::Opus => 96,
+        }
+    }
+
+    /// Atributo `codecs` da Representation (RFC 6381)
+    fn rfc6381(&self) -> &'static str {
+        match self {
+            DashCodec::Aac => "mp4a.40.2",
+            DashCodec::Opus => "opus",
+        }
+    }
+
+    /// MP4 fragmentado no stdout: sem `moov` no fim (que exigiria seek) e um fragmento a cada `segment_seconds`
+    pub fn ffmpeg_args(&self, bitrate_kbps: u32, segment_seconds: u32) -> Vec<String> {
+        let bitrate = format!("{}k", bitrate_kbps);
+        let fragment_microseconds = (segment_seconds as u64 * 1_000_000).to_string();
+        let encoder = match self {
+            DashCodec::Aac => "aac",
+            DashCodec::Opus => "libopus",
+        };
+
+        ffmpeg_command(&[
+            "-c:a",
+            encoder,
+            "-b:a",
+            &bitrate,
+            "-f",
+            "mp4",
+            "-movflags",
+            "empty_moov+default_base_moof",
+            "-frag_duration",
+            &fragment_microseconds,
+            "-flush_packets",
+            "1",
+            "-strict",
+            "experimental",
+        ])
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Uma caixa MP4 no começo de `bytes`: tipo, tamanho do cabeçalho e tamanho total. `None` se o cabeçalho
+/// ainda não chegou inteiro; o tamanho pode ser maior que `bytes`, e é `None` numa caixa de tamanho 0, que vai
+/// até o fim do arquivo. Um tamanho que não cabe em `usize` vira `usize::MAX`, que nenhuma caixa válida tem
+fn box_header(bytes: &[u8]) -> Option<([u8; 4], usize, Option<usize>)> {
+    let size = read_u32(bytes, 0)?;
+    let kind: [u8; 4] = bytes.get(4..8)?.try_into().ok()?;
+    let (header, size) = match size {
+        0 => return Some((kind, 8, None)),
+        1 => (16, read_u64(bytes, 8)?),
+        size => (8, size as u64),
+    };
+    Some((kind, header, Some(usize::try_from(size).unwrap_or(usize::MAX))))
+}
+
+/// As caixas filhas dentro do conteúdo de uma caixa: (tipo, início do conteúdo, fim), em posições de `bytes`
+fn child_boxes(bytes: &[u8], start: usize, end: usize) -> Vec<([u8; 4], usize, usize)> {
+    let mut children = Vec::new();
+    let mut at = start;
+    while at < end {
+        let Some((kind, header, size)) = box_header(&bytes[at..end]) else {
+            break;
+        };
+        // tamanho 0 dentro de outra caixa: vai até o fim dela
+        let size = size.unwrap_or(end - at);
+        if size < header || size > end - at {
+            break;
+        }
+        children.push((kind, at + header, at + size));
+        at += size;
+    }
+    children
+}
+
+fn find_child(bytes: &[u8], start: usize, end: usize, kind: &[u8; 4]) -> Option<(usize, usize)> {
+    child_boxes(bytes, start, end)
+        .into_iter()
+        .find(|(child, _, _)| child == kind)
+        .map(|(_, start, end)| (start, end))
+}
+
+/// O que o `moov` diz sobre a faixa de áudio
+#[derive(Clone, Copy, Debug)]
+struct TrackInfo {
+    timescale: u32,
+    /// duração padrão das amostras (`trex`), quando o fragmento não diz
+    default_sample_duration: u32,
+}
+
+fn parse_moov(moov: &[u8]) -> Option<TrackInfo> {
+    let (trak, trak_end) = find_child(moov, 8, moov.len(), b"trak")?;
+    let (mdia, mdia_end) = find_child(moov, trak, trak_end, b"mdia")?;
+    let (mdhd, _) = find_child(moov, mdia, mdia_end, b"mdhd")?;
+    // versão 1 tem datas de 64 bits antes do timescale
+    let timescale = match moov.get(mdhd)? {
+        1 => read_u32(moov, mdhd + 20)?,
+        _ => read_u32(moov, mdhd + 12)?,
+    };
+
+    let default_sample_duration = find_child(moov, 8, moov.len(), b"mvex")
+        .and_then(|(mvex, mvex_end)| find_child(moov, mvex, mvex_end, b"trex"))
+        .and_then(|(trex, _)| read_u32(moov, trex + 12))
+        .unwrap_or(0);
+
+    Some(TrackInfo {
+        timescale,
+        default_sample_duration,
+    })
+}
+
+/// Onde fica o `baseMediaDecodeTime` de um `moof` e quanto tempo o fragmento dura, na escala da faixa
+struct FragmentTiming {
+    /// posição do valor dentro do `moof` e se ele tem 64 bits
+    decode_time_at: usize,
+    decode_time_64: bool,
+    duration: u64,
+}
+
+fn parse_moof(moof: &[u8], track: &TrackInfo) -> Option<FragmentTiming> {
+    let (traf, traf_end) = find_child(moof, 8, moof.len(), b"traf")?;
+
+    let (tfhd, _) = find_child(moof, traf, traf_end, b"tfhd")?;
+    let tfhd_flags = read_u32(moof, tfhd)? & 0x00FF_FFFF;
+    let mut default_duration = track.default_sample_duration;
+    if tfhd_flags & 0x08 != 0 {
+        // depois de versão/flags e track_id vêm os campos opcionais, na ordem
+        let mut at = tfhd + 8;
+        if tfhd_flags & 0x01 != 0 {
+            at += 8;
+        }
+        if tfhd_flags & 0x02 != 0 {
+            at += 4;
+        }
+        default_duration = read_u32(moof, at)?;
+    }
+
+    let (tfdt, tfdt_end) = find_child(moof, traf, traf_end, b"tfdt")?;
+    let decode_time_64 = *moof.get(tfdt)? == 1;
+    // versão/flags e o valor precisam caber na caixa
+    if tfdt_end < tfdt + 4 + if decode_time_64 { 8 } else { 4 } {
+        return None;
+    }
+
+    let mut duration = 0;
+    for (kind, trun, _) in child_boxes(moof, traf, traf_end) {
+        if &kind != b"trun" {
+            continue;
+        }
+        let flags = read_u32(moof, trun)? & 0x00FF_FFFF;
+        let samples = read_u32(moof, trun + 4)? as u64;
+        if flags & 0x100 == 0 {
+            duration += samples * default_duration as u64;
+            continue;
+        }
+
+        let mut at = trun + 8;
+        if flags & 0x01 != 0 {
+            at += 4;
+        }
+        if flags & 0x04 != 0 {
+            at += 4;
+        }
+        let per_sample = [0x100, 0x200, 0x400, 0x800]
+            .iter()
+            .filter(|flag| flags & **flag != 0)
+            .count()
+            * 4;
+        for _ in 0..samples {
+            duration += read_u32(moof, at)? as u64;
+            at += per_sample;
+        }
+    }
+
+    Some(FragmentTiming {
+        decode_time_at: tfdt + 4,
+        decode_time_64,
+        duration,
+    })
+}
+
+struct Segment {
+    sequence: u64,
+    /// início e duração na escala da faixa
+    time: u64,
+    duration: u64,
+    data: Bytes,
+}
+
+/// Tudo o que muda a cada fragmento que chega
+#[derive(Default)]
+struct DashState {
+    /// bytes que ainda não formam uma caixa inteira
+    pending: Vec<u8>,
+    /// `ftyp` esperando o `moov`
+    ftyp: Vec<u8>,
+    init: Option<Bytes>,
+    track: Option<TrackInfo>,
+    moof: Option<Vec<u8>>,
+    segments: VecDeque<Segment>,
+    next_sequence: u64,
+    /// onde o próximo fragmento começa na nossa linha do tempo, que não volta a zero quando o ffmpeg reinicia
+    next_time: u64,
+    /// momento em que a mídia no tempo zero estaria no ar
+    available_since: Option<SystemTime>,
+}
+
+/// A saída DASH de uma estação: recebe o MP4 fragmentado do seu próprio ffmpeg e guarda os últimos segmentos em memória
+pub struct DashStream {
+    codec: DashCodec,
+    // nome do mount (estação/dash), para os logs
+    mount: String,
+    station: String,
+    bitrate_kbps: u32,
+    segment_seconds: u32,
+    // quantos segmentos aparecem no manifesto
+    window: usize,
+    access: StreamAccess,
+    state: RwLock<DashState>,
+    // o stdout do ffmpeg é lido por uma thread só, mas o `push` só precisa de `&self`
+    feeding: Mutex<()>,
+    counters: MountCounters,
+}
+
+impl DashStream {
+    pub fn new(station: &str, config: &DashConfig, access: StreamAccess) -> DashStream {
+        DashStream {
+            codec: config.codec,
+            mount: format!("{}/dash", station),
+            station: station.to_owned(),
+            bitrate_kbps: config.bitrate_kbps,
+            segment_seconds: config.segment_seconds,
+            window: config.window,
+            access,
+            state: RwLock::new(DashState::default()),
+            feeding: Mutex::new(()),
+            counters: MountCounters::default(),
+        }
+    }
+
+    pub fn codec(&self) -> DashCodec {
+        self.codec
+    }
+
+    pub fn segment_seconds(&self) -> u32 {
+        self.segment_seconds
+    }
+
+    /// Manifesto dinâmico com os últimos `window` segmentos. O token do pedido, se houver, vai junto nas URLs
+    pub fn manifest(&self, token: Option<&str>) -> Result<SegmentResponse, SegmentRefused> {
+        self.check_access(token)?;
+
+        let state = self.state.read().unwrap();
+        let (Some(track), Some(available_since)) = (state.track, state.available_since) else {
+            return Err(SegmentRefused::NotReady);
+        };
+        let listed: Vec<&Segment> = state
+            .segments
+            .iter()
+            .skip(state.segments.len().saturating_sub(self.window))
+            .collect();
+        if listed.len() < MIN_SEGMENTS_TO_START.min(self.window) {
+            return Err(SegmentRefused::NotReady);
+        }
+
+        let query = token.map(|token| format!("?token={}", token)).unwrap_or_default();
+        let mut timeline = String::new();
+        for segment in &listed {
+            timeline += &format!("          <S t=\"{}\" d=\"{}\"/>\n", segment.time, segment.duration);
+        }
+
+        let manifest = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" profiles="urn:mpeg:dash:profile:isoff-live:2011" type="dynamic" availabilityStartTime="{available_since}" publishTime="{now}" minimumUpdatePeriod="PT{segment}S" minBufferTime="PT{segment}S" timeShiftBufferDepth="PT{depth}S" suggestedPresentationDelay="PT{delay}S">
+  <Period id="0" start="PT0S">
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" segmentAlignment="true" lang="und">
+      <Representation id="{codec}" codecs="{codecs}" bandwidth="{bandwidth}" audioSamplingRate="{sample_rate}">
+        <AudioChannelConfiguration schemeIdUri="urn:mpeg:dash:23003:3:audio_channel_configuration:2011" value="2"/>
+        <SegmentTemplate timescale="{timescale}" initialization="init.mp4{query}" media="$Number$.m4s{query}" startNumber="{start}">
+          <SegmentTimeline>
+{timeline}          </SegmentTimeline>
+        </SegmentTemplate>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>
+"#,
+            available_since = xml_datetime(available_since),
+            now = xml_datetime(SystemTime::now()),
+            segment = self.segment_seconds,
+            depth = self.segment_seconds as usize * self.window,
+            delay = self.segment_seconds as usize * MIN_SEGMENTS_TO_START,
+            codec = self.codec.name(),
+            codecs = self.codec.rfc6381(),
+            bandwidth = self.bitrate_kbps * 1000,
+            sample_rate = track.timescale,
+            timescale = track.timescale,
+            start = listed[0].sequence,
+        );
+
+        Ok(SegmentResponse {
+            body: manifest.into_bytes(),
+            content_type: ContentType::new("application", "dash+xml"),
+            cache_control: Header::new("Cache-Control", "no-cache"),
+        })
+    }
+
+    /// O segmento de inicialização (`init.mp4`) ou um de mídia (`<número>.m4s`)
+    pub fn segment(&self, name: &str, token: Option<&str>) -> Result<SegmentResponse, SegmentRefused> {
+        self.check_access(token)?;
+
+        let state = self.state.read().unwrap();
+        let data = if name == "init.mp4" {
+            state.init.clone().ok_or(SegmentRefused::NotReady)?
+        } else {
+            let sequence: u64 = name
+                .strip_suffix(".m4s")
+                .and_then(|sequence| sequence.parse().ok())
+                .ok_or(SegmentRefused::UnknownSegment)?;
+            state
+                .segments
+                .iter()
+                .find(|segment| segment.sequence == sequence)
+                .map(|segment| segment.data.clone())
+                .ok_or(SegmentRefused::UnknownSegment)?
+        };
+        self.counters.bytes_sent.add(data.len());
+
+        // o `init.mp4` muda quando o ffmpeg é reiniciado; os segmentos de mídia, não
+        let cache_control = if name == "init.mp4" { "no-cache" } else { "max-age=3600" };
+        Ok(SegmentResponse {
+            body: data.to_vec(),
+            content_type: ContentType::new("audio", "mp4"),
+            cache_control: Header::new("Cache-Control", cache_control),
+        })
+    }
+
+    fn check_access(&self, token: Option<&str>) -> Result<(), SegmentRefused> {
+        self.access
+            .check(token, &self.station)
+            .map(|_| ())
+            .map_err(SegmentRefused::Unauthorized)
+    }
+
+    /// Uma caixa de topo inteira que saiu do ffmpeg
+    fn take_box(&self, state: &mut DashState, kind: [u8; 4], data: Vec<u8>) {
+        match &kind {
+            b"ftyp" => state.ftyp = data,
+            b"moov" => {
+                state.track = parse_moov(&data);
+                if state.track.is_none() {
+                    warn!(mount = %self.mount, "moov sem faixa de áudio reconhecível");
+                }
+                let mut init = std::mem::take(&mut state.ftyp);
+                init.extend_from_slice(&data);
+                state.init = Some(Bytes::from(init));
+            }
+            b"moof" => state.moof = Some(data),
+            b"mdat" => {
+                let (Some(mut moof), Some(track)) = (state.moof.take(), state.track) else {
+                    return;
+                };
+                let Some(timing) = parse_moof(&moof, &track) else {
+                    warn!(mount = %self.mount, "moof ilegível, fragmento descartado");
+                    return;
+                };
+
+                // o fragmento passa a começar onde o anterior terminou
+                let time = state.next_time;
+                let at = timing.decode_time_at;
+                let written = if timing.decode_time_64 {
+                    moof.get_mut(at..at + 8)
+                        .map(|field| field.copy_from_slice(&time.to_be_bytes()))
+                } else {
+                    moof.get_mut(at..at + 4)
+                        .map(|field| field.copy_from_slice(&(time as u32).to_be_bytes()))
+                };
+                if written.is_none() {
+                    warn!(mount = %self.mount, "tfdt curto demais, fragmento descartado");
+                    return;
+                }
+                state.next_time += timing.duration;
+                if state.available_since.is_none() {
+                    let elapsed = Duration::from_secs_f64(state.next_time as f64 / track.timescale as f64);
+                    state.available_since = Some(SystemTime::now() - elapsed);
+                }
+
+                moof.extend_from_slice(&data);
+                let segment = Segment {
+                    sequence: state.next_sequence,
+                    time,
+                    duration: timing.duration,
+                    data: Bytes::from(moof),
+                };
+                debug!(
+                    mount = %self.mount,
+                    sequence = segment.sequence,
+                    seconds = segment.duration as f64 / track.timescale as f64,
+                    bytes = segment.data.len(),
+                    "segmento DASH pronto"
+                );
+                state.next_sequence += 1;
+                state.segments.push_back(segment);
+                while state.segments.len() > self.window + SEGMENTS_KEPT_AFTER_WINDOW {
+                    state.segments.pop_front();
+                }
+            }
+            kind if IGNORED_BOXES.contains(&kind) => {}
+            kind => debug!(mount = %self.mount, kind = %String::from_utf8_lossy(kind), "caixa MP4 ignorada"),
+        }
+    }
+}
+
+/// `AAAA-MM-DDThh:mm:ssZ`, como o MPD quer
+fn xml_datetime(time: SystemTime) -> String {
+    let time = OffsetDateTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+impl EncodedOutput for DashStream {
+    fn push(&self, packet: Bytes) {
+        let _feeding = self.feeding.lock().unwrap();
+
+        let mut pending = std::mem::take(&mut self.state.write().unwrap().pending);
+        pending.extend_from_slice(&packet);
+
+        let mut offset = 0;
+        while let Some((kind, header, size)) = box_header(&pending[offset..]) {
+            let Some(size) = size else {
+                // tamanho 0: a caixa vai até o fim da saída deste ffmpeg, e é entregue quando ele for reiniciado
+                if pending.len() - offset > MAX_BOX_BYTES {
+                    warn!(mount = %self.mount, "caixa sem tamanho grande demais, descartando");
+                    offset = pending.len();
+                }
+                break;
+            };
+            if size < header || size > MAX_BOX_BYTES {
+                // sem como achar a próxima caixa; o que vier depois do próximo reinício do ffmpeg volta a fazer sentido
+                warn!(mount = %self.mount, size, "saída do ffmpeg fora de sincronia, descartando");
+                offset = pending.len();
+                break;
+            }
+            if pending.len() - offset < size {
+                break;
+            }
+            let data = pending[offset..offset + size].to_vec();
+            offset += size;
+            self.take_box(&mut self.state.write().unwrap(), kind, data);
+        }
+        pending.drain(..offset);
+
+        self.state.write().unwrap().pending = pending;
+    }
+
+    fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    fn counters(&self) -> &MountCounters {
+        &self.counters
+    }
+
+    fn restarted(&self) {
+        let _feeding = self.feeding.lock().unwrap();
+        let mut state = self.state.write().unwrap();
+        // uma caixa de tamanho 0 do ffmpeg anterior terminou junto com ele
+        let pending = std::mem::take(&mut state.pending);
+        if let Some((kind, _, None)) = box_header(&pending) {
+            self.take_box(&mut state, kind, pending);
+        }
+        state.moof = None;
+    }
+}
@@ -0,0 +1,261 @@
+// FLAC sem perdas, no container nativo. O decoder precisa do cabeçalho `fLaC` com o bloco STREAMINFO antes de
+// qualquer frame, e o ffmpeg só o escreve uma vez, no começo; então o cabeçalho fica guardado no mount para os
+// clientes que chegam depois, e o áudio é repassado em frames inteiros, para que cada cliente comece num frame
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use tracing::{debug, warn};
+
+use crate::output_encoder::audio_encoder::EncodedOutput;
+
+use super::{MountCounters, OutputStream};
+
+const STREAM_MARKER: &[u8] = b"fLaC";
+const BLOCK_STREAMINFO: u8 = 0;
+const STREAMINFO_BYTES: usize = 34;
+/// o maior cabeçalho de frame possível: sync, 2 bytes de códigos, número UTF-8 de até 7 bytes, tamanho do bloco e
+/// taxa estendidos (2 + 2) e o CRC-8
+const MAX_FRAME_HEADER_BYTES: usize = 16;
+/// o maior frame do subset "streamable", que é o que o ffmpeg gera: 16384 amostras de 8 canais de 24 bits sem
+/// compressão dão 384 KiB, mais cabeçalhos e folga. Sem um CRC batendo até aqui, o sync era falso
+const MAX_FRAME_BYTES: usize = 512 * 1024;
+
+/// O que sai do ffmpeg, separado em cabeçalho e frames
+pub enum FlacChunk {
+    /// `fLaC` e só o STREAMINFO, marcado como último bloco; os outros blocos (comentários, padding) não fazem
+    /// falta para tocar e o padding do ffmpeg tem 8 KiB
+    Header(Bytes),
+    /// um ou mais frames inteiros
+    Frames(Bytes),
+}
+
+/// Acha os limites dos frames num stream FLAC que chega em pedaços quaisquer
+#[derive(Default)]
+pub struct FlacFramer {
+    pending: Vec<u8>,
+    /// já houve um cabeçalho: só a partir dele os frames fazem sentido para os clientes
+    has_header: bool,
+}
+
+impl FlacFramer {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<FlacChunk> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut chunks = Vec::new();
+        loop {
+            if self.pending.starts_with(STREAM_MARKER) {
+                match parse_header(&self.pending) {
+                    Some((header, consumed)) => {
+                        self.pending.drain(..consumed);
+                        self.has_header = true;
+                        chunks.push(FlacChunk::Header(header));
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
+            // o maior cabeçalho de frame ainda pode estar chegando
+            if self.pending.len() < MAX_FRAME_HEADER_BYTES {
+                break;
+            }
+            let Some(header_len) = frame_header_len(&self.pending) else {
+                // fora de sincronia: pula até o próximo cabeçalho de stream ou de frame
+                match self.resync_offset() {
+                    Some(offset) => {
+                        debug!(skipped = offset, "flac fora de sincronia");
+                        self.pending.drain(..offset);
+                        continue;
+                    }
+                    None => {
+                        // só o fim pode ser o começo de um cabeçalho que ainda está chegando
+                        let skipped = self.pending.len() - (MAX_FRAME_HEADER_BYTES - 1);
+                        debug!(skipped, "flac fora de sincronia");
+                        self.pending.drain(..skipped);
+                        break;
+                    }
+                }
+            };
+
+            // um frame termina onde o próximo começa, e só é dado como inteiro se o CRC-16 dele bater; vão todos
+            // os frames inteiros de uma vez
+            let end = (header_len + 2..self.pending.len())
+                .rev()
+                .find(|&at| frame_header_len(&self.pending[at..]).is_some() && crc16(&self.pending[..at]) == 0);
+            if let Some(end) = end {
+                let frames: Vec<u8> = self.pending.drain(..end).collect();
+                if self.has_header {
+                    chunks.push(FlacChunk::Frames(Bytes::from(frames)));
+                }
+            } else if self.pending.len() > MAX_FRAME_BYTES {
+                // nenhum CRC bateu dentro do maior frame possível: sync falso ou frame corrompido, então o
+                // `pending` não cresce para sempre esperando por ele
+                debug!("flac: frame sem CRC válido, procurando o próximo");
+                self.pending.drain(..1);
+                continue;
+            }
+            break;
+        }
+
+        chunks
+    }
+
+    /// O ffmpeg foi reiniciado: o frame pela metade do anterior não vai ser completado
+    pub fn reset(&mut self) {
+        self.pending.clear();
+    }
+
+    fn resync_offset(&self) -> Option<usize> {
+        (1..self.pending.len()).find(|&at| {
+            let rest = &self.pending[at..];
+            rest.starts_with(STREAM_MARKER) || frame_header_len(rest).is_some()
+        })
+    }
+}
+
+/// O cabeçalho reduzido ao STREAMINFO e quantos bytes ele ocupava; `None` se ainda não chegou inteiro
+fn parse_header(bytes: &[u8]) -> Option<(Bytes, usize)> {
+    let mut offset = STREAM_MARKER.len();
+    let mut stream_info = None;
+    loop {
+        let block = bytes.get(offset..offset + 4)?;
+        let last = block[0] & 0x80 != 0;
+        let kind = block[0] & 0x7F;
+        let len = u32::from_be_bytes([0, block[1], block[2], block[3]]) as usize;
+        let body = bytes.get(offset + 4..offset + 4 + len)?;
+        if kind == BLOCK_STREAMINFO && len == STREAMINFO_BYTES {
+            stream_info = Some(body);
+        }
+        offset += 4 + len;
+        if last {
+            break;
+        }
+    }
+
+    let Some(stream_info) = stream_info else {
+        warn!("cabeçalho flac sem STREAMINFO, repassado como veio");
+        return Some((Bytes::copy_from_slice(&bytes[..offset]), offset));
+    };
+    let mut header = Vec::with_capacity(STREAM_MARKER.len() + 4 + STREAMINFO_BYTES);
+    header.extend_from_slice(STREAM_MARKER);
+    header.push(0x80 | BLOCK_STREAMINFO);
+    header.extend_from_slice(&(STREAMINFO_BYTES as u32).to_be_bytes()[1..]);
+    header.extend_from_slice(stream_info);
+    Some((Bytes::from(header), offset))
+}
+
+fn is_frame_sync(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xFE == 0xF8
+}
+
+/// Tamanho do cabeçalho de frame no começo de `bytes`, se for um cabeçalho válido e com o CRC-8 certo
+fn frame_header_len(bytes: &[u8]) -> Option<usize> {
+    if !is_frame_sync(bytes) {
+        return None;
+    }
+    let &[_, _, sizes, format, ..] = bytes else {
+        return None;
+    };
+    let block_size_code = sizes >> 4;
+    let sample_rate_code = sizes & 0x0F;
+    let channels = format >> 4;
+    // códigos reservados ou inválidos
+    if block_size_code == 0 || sample_rate_code == 0x0F || channels > 10 || format & 0x01 != 0 {
+        return None;
+    }
+
+    // número do frame (ou da amostra) em UTF-8 estendido: o primeiro byte diz quantos vêm depois
+    let first = *bytes.get(4)?;
+    let extra = match first.leading_ones() {
+        0 => 0,
+        ones @ 2..=7 => ones as usize - 1,
+        _ => return None,
+    };
+    let mut len = 5 + extra;
+    if !bytes.get(5..len)?.iter().all(|byte| byte & 0xC0 == 0x80) {
+        return None;
+    }
+
+    len += match block_size_code {
+        6 => 1,
+        7 => 2,
+        _ => 0,
+    };
+    len += match sample_rate_code {
+        12 => 1,
+        13 | 14 => 2,
+        _ => 0,
+    };
+
+    let crc = *bytes.get(len)?;
+    (crc8(&bytes[..len]) == crc).then_some(len + 1)
+}
+
+/// CRC-8 do cabeçalho de frame: polinômio x^8 + x^2 + x + 1, começando em 0
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// CRC-16 do frame: polinômio x^16 + x^15 + x^2 + 1, começando em 0. Sobre o frame inteiro, com o CRC que vem no
+/// fim dele, dá 0
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// A saída FLAC de um mount: guarda o cabeçalho no `OutputStream`, para os clientes novos, e repassa os frames
+pub struct FlacOutput {
+    stream: Arc<OutputStream>,
+    framer: Mutex<FlacFramer>,
+}
+
+impl FlacOutput {
+    pub fn new(stream: Arc<OutputStream>) -> FlacOutput {
+        FlacOutput {
+            stream,
+            framer: Mutex::new(FlacFramer::default()),
+        }
+    }
+}
+
+impl EncodedOutput for FlacOutput {
+    fn push(&self, packet: Bytes) {
+        let chunks = self.framer.lock().unwrap().push(&packet);
+        for chunk in chunks {
+            match chunk {
+                // depois de um reinício do ffmpeg vem um cabeçalho novo, igual ao anterior; quem já está ouvindo
+                // não o recebe, só quem conectar depois
+                FlacChunk::Header(header) => self.stream.set_stream_header(header),
+                FlacChunk::Frames(frames) => self.stream.push(frames),
+            }
+        }
+    }
+
+    fn mount(&self) -> &str {
+        self.stream.mount()
+    }
+
+    fn counters(&self) -> &MountCounters {
+        self.stream.counters()
+    }
+
+    fn restarted(&self) {
+        self.framer.lock().unwrap().reset();
+    }
+}
@@ -0,0 +1,202 @@
+// proteção contra flood nos endpoints de stream: limita os streams simultâneos e as conexões novas por endereço,
+// bane temporariamente quem abre conexões rápido demais e conta todas as recusas
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+use serde::Serialize;
+
+use tracing::{info, warn};
+
+use crate::config::FloodConfig;
+
+use super::{
+    bans::{BanList, NotBanned},
+    client::client_address,
+};
+
+/// janela da contagem de conexões novas
+const CONNECT_WINDOW: Duration = Duration::from_secs(60);
+
+/// o que um endereço está fazendo: streams abertos agora e quando abriu os últimos
+#[derive(Default)]
+struct IpActivity {
+    open: usize,
+    recent: VecDeque<Instant>,
+}
+
+type ActivityMap = Arc<Mutex<HashMap<IpAddr, IpActivity>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloodRejection {
+    /// o endereço já tem `max_connections_per_ip` streams abertos
+    TooManyConnections,
+    /// o endereço abriu mais de `max_connects_per_minute` conexões no último minuto; vira ban
+    TooManyConnects,
+}
+
+impl fmt::Display for FloodRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FloodRejection::TooManyConnections => write!(f, "conexões simultâneas demais"),
+            FloodRejection::TooManyConnects => write!(f, "conexões novas demais"),
+        }
+    }
+}
+
+/// Quantas conexões foram recusadas desde que o servidor subiu, por motivo
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct FloodRejections {
+    pub banned: usize,
+    pub too_many_connections: usize,
+    pub too_many_connects: usize,
+}
+
+pub struct FloodGuard {
+    config: FloodConfig,
+    activity: ActivityMap,
+    banned: AtomicUsize,
+    too_many_connections: AtomicUsize,
+    too_many_connects: AtomicUsize,
+}
+
+impl FloodGuard {
+    pub fn new(config: FloodConfig) -> Self {
+        FloodGuard {
+            config,
+            activity: Arc::default(),
+            banned: AtomicUsize::new(0),
+            too_many_connections: AtomicUsize::new(0),
+            too_many_connects: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn ban_duration(&self) -> Duration {
+        // a configuração já limita `ban_minutes` a `MAX_BAN_MINUTES`
+        self.config
+            .ban_minutes
+            .checked_mul(60)
+            .map_or(Duration::MAX, Duration::from_secs)
+    }
+
+    /// Registra uma conexão nova de `address`. Se couber, o endereço ganha um stream aberto até o permit ser solto
+    pub fn admit(&self, address: IpAddr) -> Result<ConnectionPermit, FloodRejection> {
+        let now = Instant::now();
+        let mut activity = self.activity.lock().unwrap();
+        // esquece endereços sem stream aberto e sem conexão recente
+        activity.retain(|_, ip| {
+            ip.open > 0 || ip.recent.back().is_some_and(|at| now - *at < CONNECT_WINDOW)
+        });
+
+        let ip = activity.entry(address).or_default();
+        while ip.recent.front().is_some_and(|at| now - *at >= CONNECT_WINDOW) {
+            ip.recent.pop_front();
+        }
+        // toda tentativa conta para a taxa, mesmo as recusadas
+        ip.recent.push_back(now);
+
+        let rejection = if ip.recent.len() > self.config.max_connects_per_minute {
+            Some(FloodRejection::TooManyConnects)
+        } else if ip.open >= self.config.max_connections_per_ip {
+            Some(FloodRejection::TooManyConnections)
+        } else {
+            None
+        };
+
+        if let Some(rejection) = rejection {
+            warn!(
+                %address,
+                open = ip.open,
+                last_minute = ip.recent.len(),
+                "conexão recusada: {}",
+                rejection
+            );
+            match rejection {
+                FloodRejection::TooManyConnections => &self.too_many_connections,
+                FloodRejection::TooManyConnects => &self.too_many_connects,
+            }
+            .fetch_add(1, Ordering::Relaxed);
+            return Err(rejection);
+        }
+
+        ip.open += 1;
+        Ok(ConnectionPermit {
+            held: Some((address, Arc::clone(&self.activity))),
+        })
+    }
+
+    pub fn rejections(&self) -> FloodRejections {
+        FloodRejections {
+            banned: self.banned.load(Ordering::Relaxed),
+            too_many_connections: self.too_many_connections.load(Ordering::Relaxed),
+            too_many_connects: self.too_many_connects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Guard dos endpoints de stream: recusa endereços banidos (403) e quem passou dos limites (429).
+/// Vai junto com o stream, e solta a vaga do endereço quando o cliente desconecta
+pub struct ConnectionPermit {
+    /// sem endereço ou sem `FloodGuard`, não há o que limitar
+    held: Option<(IpAddr, ActivityMap)>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        if let Some((address, activity)) = &self.held {
+            if let Some(ip) = activity.lock().unwrap().get_mut(address) {
+                ip.open = ip.open.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ConnectionPermit {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, String> {
+        let flood = request.rocket().state::<FloodGuard>();
+
+        if let Outcome::Error(error) = request.guard::<NotBanned>().await {
+            if let Some(flood) = flood {
+                flood.banned.fetch_add(1, Ordering::Relaxed);
+            }
+            info!(address = ?client_address(request), "conexão recusada: endereço banido");
+            return Outcome::Error(error);
+        }
+
+        let (Some(flood), Some(address)) = (flood, client_address(request)) else {
+            return Outcome::Success(ConnectionPermit { held: None });
+        };
+
+        match flood.admit(address) {
+            Ok(permit) => Outcome::Success(permit),
+            Err(rejection) => {
+                if rejection == FloodRejection::TooManyConnects {
+                    if let Some(bans) = request.rocket().state::<BanList>() {
+                        bans.ban(address, flood.ban_duration());
+                        warn!(
+                            %address,
+                            minutes = flood.config.ban_minutes,
+                            "endereço banido por flood"
+                        );
+                    }
+                }
+                Outcome::Error((Status::TooManyRequests, rejection.to_string()))
+            }
+        }
+    }
+}
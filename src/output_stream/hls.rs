@@ -0,0 +1,537 @@
+// HLS: o áudio codificado é cortado em segmentos de duração fixa ("packed audio": AAC em ADTS ou MP3 puro),
+// servidos com uma playlist de janela deslizante. Cada segmento começa com uma tag ID3 com o timestamp exigido
+// pelo HLS (PRIV) e a faixa no ar, que os players mostram como metadados temporizados
+
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, RwLock},
+};
+
+use bytes::Bytes;
+use rocket::http::{ContentType, Header};
+use tracing::{debug, trace};
+use web_radio::objects::track::track::Track;
+
+use crate::{
+    config::HlsConfig,
+    output_encoder::audio_encoder::{ffmpeg_command, EncodedOutput},
+};
+
+use super::{token::StreamAccess, MountCounters, SegmentRefused, SegmentResponse};
+
+/// segmentos que já saíram da playlist mas ainda podem ser baixados, para quem pegou a playlist anterior
+const SEGMENTS_KEPT_AFTER_WINDOW: usize = 2;
+/// a playlist só é servida com pelo menos esses segmentos, senão o player começa sem margem e trava
+const MIN_SEGMENTS_TO_START: usize = 3;
+/// relógio dos timestamps do HLS (o mesmo do MPEG-TS)
+const TIMESTAMP_HZ: u64 = 90_000;
+const ID3_TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum HlsCodec {
+    Aac,
+    Mp3,
+}
+
+impl HlsCodec {
+    pub const ALL: [HlsCodec; 2] = [HlsCodec::Aac, HlsCodec::Mp3];
+
+    /// Nome curto do codec, usado no arquivo de configuração
+    pub fn name(&self) -> &'static str {
+        match self {
+            HlsCodec::Aac => "aac",
+            HlsCodec::Mp3 => "mp3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HlsCodec> {
+        Self::ALL
+            .iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    pub fn default_bitrate_kbps(&self) -> u32 {
+        match self {
+            HlsCodec::Aac => 128,
+            HlsCodec::Mp3 => 128,
+        }
+    }
+
+    /// Extensão dos segmentos
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            HlsCodec::Aac => ContentType::new("audio", "aac"),
+            HlsCodec::Mp3 => ContentType::new("audio", "mpeg"),
+        }
+    }
+
+    pub fn ffmpeg_args(&self, bitrate_kbps: u32) -> Vec<String> {
+        let bitrate = format!("{}k", bitrate_kbps);
+
+        ffmpeg_command(&match self {
+            HlsCodec::Aac => vec!["-c:a", "aac", "-b:a", &bitrate, "-f", "adts"],
+            HlsCodec::Mp3 => vec![
+                "-c:a",
+                "libmp3lame",
+                "-b:a",
+                &bitrate,
+                "-f",
+                "mp3",
+                "-flush_packets",
+                "1",
+                "-write_xing",
+                "0",
+                "-id3v2_version",
+                "0",
+            ],
+        })
+    }
+
+    /// Lê o cabeçalho do frame no começo de `bytes`; `None` se ali não começa um frame
+    fn parse_frame(&self, bytes: &[u8]) -> Option<AudioFrame> {
+        match self {
+            HlsCodec::Aac => parse_adts_frame(bytes),
+            HlsCodec::Mp3 => parse_mp3_frame(bytes),
+        }
+    }
+}
+
+/// Um frame de áudio codificado: quantos bytes ocupa e quantas amostras carrega
+#[derive(Clone, Copy, Debug)]
+struct AudioFrame {
+    length: usize,
+    samples: u64,
+    sample_rate: u32,
+}
+
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+fn parse_adts_frame(bytes: &[u8]) -> Option<AudioFrame> {
+    if bytes.len() < 7 || bytes[0] != 0xFF || bytes[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+    let header_length = if bytes[1] & 0x01 == 1 { 7 } else { 9 };
+    let sample_rate = *ADTS_SAMPLE_RATES.get(((bytes[2] >> 2) & 0x0F) as usize)?;
+    let length = ((bytes[3] as usize & 0x03) << 11) | ((bytes[4] as usize) << 3) | (bytes[5] as usize >> 5);
+    let blocks = (bytes[6] & 0x03) as u64 + 1;
+
+    (length > header_length).then_some(AudioFrame {
+        length,
+        samples: 1024 * blocks,
+        sample_rate,
+    })
+}
+
+/// kbps por índice, Layer III
+const MP3_BITRATES_V1: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MP3_BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+fn parse_mp3_frame(bytes: &[u8]) -> Option<AudioFrame> {
+    if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = (bytes[1] >> 3) & 0x03;
+    let layer = (bytes[1] >> 1) & 0x03;
+    // só Layer III; versão 1 é reservada
+    if layer != 1 || version == 1 {
+        return None;
+    }
+    let mpeg1 = version == 3;
+
+    let bitrates = if mpeg1 { &MP3_BITRATES_V1 } else { &MP3_BITRATES_V2 };
+    let bitrate = *bitrates.get((bytes[2] >> 4) as usize).filter(|bitrate| **bitrate > 0)?;
+    let base_rate = [44100, 48000, 32000].get(((bytes[2] >> 2) & 0x03) as usize)?;
+    let sample_rate = match version {
+        3 => *base_rate,
+        2 => base_rate / 2,
+        _ => base_rate / 4,
+    };
+    let padding = ((bytes[2] >> 1) & 0x01) as usize;
+    let (coefficient, samples) = if mpeg1 { (144_000, 1152) } else { (72_000, 576) };
+
+    Some(AudioFrame {
+        length: (coefficient * bitrate / sample_rate) as usize + padding,
+        samples,
+        sample_rate,
+    })
+}
+
+/// Áudio de um segmento, ainda sem a tag ID3
+struct EncodedSegment {
+    /// posição do primeiro frame na linha do tempo do mount, em amostras
+    start_samples: u64,
+    sample_rate: u32,
+    samples: u64,
+    audio: Vec<u8>,
+    /// primeiro segmento depois de um reinício do ffmpeg
+    discontinuity: bool,
+    /// faixa no ar quando o segmento começou
+    tags: TrackTags,
+}
+
+/// Junta os bytes que saem do ffmpeg em frames inteiros e fecha um segmento a cada `target_seconds` de áudio.
+/// Bytes que não formam um frame (ex.: o começo de um ffmpeg reiniciado) são descartados até achar o próximo.
+/// Depois de um reinício, o áudio do ffmpeg novo começa num segmento próprio, marcado como descontinuidade
+struct Segmenter {
+    codec: HlsCodec,
+    target_seconds: f64,
+    pending: Vec<u8>,
+    current: Vec<u8>,
+    current_samples: u64,
+    sample_rate: u32,
+    timeline_samples: u64,
+    /// o ffmpeg foi reiniciado e ainda não chegou nenhum frame do novo
+    restarted: bool,
+    /// o segmento atual começou depois de um reinício
+    discontinuity: bool,
+    /// faixa no ar quando o segmento atual começou; um segmento que atravessa a troca de faixa fica com a anterior
+    current_tags: TrackTags,
+}
+
+impl Segmenter {
+    fn new(codec: HlsCodec, target_seconds: f64) -> Segmenter {
+        Segmenter {
+            codec,
+            target_seconds,
+            pending: Vec::new(),
+            current: Vec::new(),
+            current_samples: 0,
+            sample_rate: 0,
+            timeline_samples: 0,
+            restarted: false,
+            discontinuity: false,
+            current_tags: TrackTags::default(),
+        }
+    }
+
+    /// `tags` é a faixa no ar agora, guardada para os segmentos que começarem neste pedaço
+    fn push(&mut self, data: &[u8], tags: &TrackTags) -> Vec<EncodedSegment> {
+        self.pending.extend_from_slice(data);
+
+        let mut finished = Vec::new();
+        let mut offset = 0;
+        let mut skipped = 0;
+        while offset < self.pending.len() {
+            let rest = &self.pending[offset..];
+            let Some(frame) = self.codec.parse_frame(rest) else {
+                // o cabeçalho pode só estar incompleto
+                if rest.len() < 9 {
+                    break;
+                }
+                offset += 1;
+                skipped += 1;
+                continue;
+            };
+            if frame.length > rest.len() {
+                break;
+            }
+            if self.restarted {
+                // os frames do ffmpeg anterior fecham um segmento mais curto
+                if !self.current.is_empty() {
+                    finished.push(self.cut());
+                }
+                self.restarted = false;
+                self.discontinuity = true;
+            }
+            if self.current.is_empty() {
+                self.current_tags = tags.clone();
+            }
+
+            self.current.extend_from_slice(&self.pending[offset..offset + frame.length]);
+            self.current_samples += frame.samples;
+            self.sample_rate = frame.sample_rate;
+            offset += frame.length;
+
+            if self.current_samples as f64 >= self.target_seconds * self.sample_rate as f64 {
+                finished.push(self.cut());
+            }
+        }
+        self.pending.drain(..offset);
+
+        if skipped > 0 {
+            trace!(bytes = skipped, "bytes fora de frame descartados");
+        }
+        finished
+    }
+
+    fn cut(&mut self) -> EncodedSegment {
+        let segment = EncodedSegment {
+            start_samples: self.timeline_samples,
+            sample_rate: self.sample_rate,
+            samples: self.current_samples,
+            audio: std::mem::take(&mut self.current),
+            discontinuity: std::mem::take(&mut self.discontinuity),
+            tags: std::mem::take(&mut self.current_tags),
+        };
+        self.timeline_samples += self.current_samples;
+        self.current_samples = 0;
+        segment
+    }
+
+    /// O ffmpeg foi reiniciado: o frame pela metade do anterior não vai ser completado, e o próximo frame
+    /// recomeça timestamps e estado do codec
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.restarted = true;
+    }
+}
+
+/// Faixa no ar, como vai nas tags ID3 e no `#EXTINF`
+#[derive(Clone, Default)]
+struct TrackTags {
+    title: String,
+    artist: String,
+    album: String,
+}
+
+impl TrackTags {
+    fn stream_title(&self) -> String {
+        match (self.artist.is_empty(), self.title.is_empty()) {
+            (false, false) => format!("{} - {}", self.artist, self.title),
+            (true, _) => self.title.clone(),
+            (false, true) => self.artist.clone(),
+        }
+    }
+}
+
+/// Tag ID3v2.4 do começo de cada segmento: o timestamp do primeiro frame (90 kHz, 33 bits) e a faixa no ar
+fn id3_tag(timestamp: u64, tags: &TrackTags) -> Vec<u8> {
+    fn frame(frames: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+        frames.extend_from_slice(id);
+        frames.extend_from_slice(&syncsafe(data.len()));
+        frames.extend_from_slice(&[0, 0]);
+        frames.extend_from_slice(data);
+    }
+
+    let mut frames = Vec::new();
+    let mut timestamp_data = ID3_TIMESTAMP_OWNER.to_vec();
+    timestamp_data.extend_from_slice(&(timestamp & 0x1_FFFF_FFFF).to_be_bytes());
+    frame(&mut frames, b"PRIV", &timestamp_data);
+
+    for (id, text) in [(b"TIT2", &tags.title), (b"TPE1", &tags.artist), (b"TALB", &tags.album)] {
+        if !text.is_empty() {
+            // 3: UTF-8
+            let mut data = vec![3];
+            data.extend_from_slice(text.as_bytes());
+            frame(&mut frames, id, &data);
+        }
+    }
+
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend_from_slice(&syncsafe(frames.len()));
+    tag.extend_from_slice(&frames);
+    tag
+}
+
+/// Inteiro de 28 bits em 4 bytes de 7 bits, como o ID3 quer
+fn syncsafe(value: usize) -> [u8; 4] {
+    [
+        ((value >> 21) & 0x7F) as u8,
+        ((value >> 14) & 0x7F) as u8,
+        ((value >> 7) & 0x7F) as u8,
+        (value & 0x7F) as u8,
+    ]
+}
+
+struct Segment {
+    sequence: u64,
+    seconds: f64,
+    title: String,
+    data: Bytes,
+    /// vem depois de um reinício do ffmpeg: leva `#EXT-X-DISCONTINUITY` na playlist
+    discontinuity: bool,
+    /// quantas descontinuidades vieram antes deste segmento, para o `#EXT-X-DISCONTINUITY-SEQUENCE`
+    discontinuity_sequence: u64,
+}
+
+/// A saída HLS de uma estação: recebe o áudio do seu próprio ffmpeg e guarda os últimos segmentos em memória
+pub struct HlsStream {
+    codec: HlsCodec,
+    // nome do mount (estação/hls), para os logs
+    mount: String,
+    station: String,
+    segment_seconds: u32,
+    // quantos segmentos aparecem na playlist
+    window: usize,
+    access: StreamAccess,
+    segmenter: Mutex<Segmenter>,
+    segments: RwLock<VecDeque<Segment>>,
+    next_sequence: Mutex<u64>,
+    tags: RwLock<TrackTags>,
+    counters: MountCounters,
+}
+
+impl HlsStream {
+    pub fn new(station: &str, config: &HlsConfig, access: StreamAccess) -> HlsStream {
+        HlsStream {
+            codec: config.codec,
+            mount: format!("{}/hls", station),
+            station: station.to_owned(),
+            segment_seconds: config.segment_seconds,
+            window: config.window,
+            access,
+            segmenter: Mutex::new(Segmenter::new(config.codec, config.segment_seconds as f64)),
+            segments: RwLock::new(VecDeque::new()),
+            next_sequence: Mutex::new(0),
+            tags: RwLock::new(TrackTags::default()),
+            counters: MountCounters::default(),
+        }
+    }
+
+    pub fn codec(&self) -> HlsCodec {
+        self.codec
+    }
+
+    pub fn segment_seconds(&self) -> u32 {
+        self.segment_seconds
+    }
+
+    /// Faixa que vai nas tags dos próximos segmentos
+    pub fn set_track(&self, track: &Track) {
+        *self.tags.write().unwrap() = TrackTags {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+        };
+    }
+
+    /// Playlist de mídia com os últimos `window` segmentos. O token do pedido, se houver, vai junto nas URLs
+    /// dos segmentos, que também são conferidas
+    pub fn playlist(&self, token: Option<&str>) -> Result<SegmentResponse, SegmentRefused> {
+        self.check_access(token)?;
+
+        let segments = self.segments.read().unwrap();
+        let listed: Vec<&Segment> = segments
+            .iter()
+            .skip(segments.len().saturating_sub(self.window))
+            .collect();
+        if listed.len() < MIN_SEGMENTS_TO_START.min(self.window) {
+            return Err(SegmentRefused::NotReady);
+        }
+
+        let query = token.map(|token| format!("?token={}", token)).unwrap_or_default();
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:{}\n\
+             #EXT-X-DISCONTINUITY-SEQUENCE:{}\n",
+            self.segment_seconds, listed[0].sequence, listed[0].discontinuity_sequence
+        );
+        for segment in listed {
+            if segment.discontinuity {
+                playlist += "#EXT-X-DISCONTINUITY\n";
+            }
+            playlist += &format!(
+                "#EXTINF:{:.3},{}\n{}.{}{}\n",
+                segment.seconds,
+                segment.title,
+                segment.sequence,
+                self.codec.extension(),
+                query
+            );
+        }
+
+        Ok(SegmentResponse {
+            body: playlist.into_bytes(),
+            content_type: ContentType::new("application", "vnd.apple.mpegurl"),
+            cache_control: Header::new("Cache-Control", "no-cache"),
+        })
+    }
+
+    /// Um segmento pelo nome (`<sequência>.<extensão>`)
+    pub fn segment(&self, name: &str, token: Option<&str>) -> Result<SegmentResponse, SegmentRefused> {
+        self.check_access(token)?;
+
+        let sequence: u64 = name
+            .strip_suffix(self.codec.extension())
+            .and_then(|name| name.strip_suffix('.'))
+            .and_then(|sequence| sequence.parse().ok())
+            .ok_or(SegmentRefused::UnknownSegment)?;
+        let data = self
+            .segments
+            .read()
+            .unwrap()
+            .iter()
+            .find(|segment| segment.sequence == sequence)
+            .map(|segment| segment.data.clone())
+            .ok_or(SegmentRefused::UnknownSegment)?;
+        self.counters.bytes_sent.add(data.len());
+
+        Ok(SegmentResponse {
+            body: data.to_vec(),
+            content_type: self.codec.content_type(),
+            cache_control: Header::new("Cache-Control", "max-age=3600"),
+        })
+    }
+
+    fn check_access(&self, token: Option<&str>) -> Result<(), SegmentRefused> {
+        self.access
+            .check(token, &self.station)
+            .map(|_| ())
+            .map_err(SegmentRefused::Unauthorized)
+    }
+}
+
+impl EncodedOutput for HlsStream {
+    fn push(&self, packet: Bytes) {
+        let finished = self
+            .segmenter
+            .lock()
+            .unwrap()
+            .push(&packet, &self.tags.read().unwrap());
+        if finished.is_empty() {
+            return;
+        }
+
+        let mut segments = self.segments.write().unwrap();
+        let mut next_sequence = self.next_sequence.lock().unwrap();
+        for encoded in finished {
+            let timestamp = encoded.start_samples * TIMESTAMP_HZ / encoded.sample_rate as u64;
+            let mut data = id3_tag(timestamp, &encoded.tags);
+            data.extend_from_slice(&encoded.audio);
+
+            let discontinuity_sequence = segments
+                .back()
+                .map_or(0, |last| last.discontinuity_sequence + last.discontinuity as u64);
+            let segment = Segment {
+                sequence: *next_sequence,
+                seconds: encoded.samples as f64 / encoded.sample_rate as f64,
+                title: encoded.tags.stream_title().replace(['\n', '\r'], " "),
+                data: Bytes::from(data),
+                discontinuity: encoded.discontinuity,
+                discontinuity_sequence,
+            };
+            debug!(
+                mount = %self.mount,
+                sequence = segment.sequence,
+                seconds = segment.seconds,
+                bytes = segment.data.len(),
+                discontinuity = segment.discontinuity,
+                "segmento HLS pronto"
+            );
+            *next_sequence += 1;
+            segments.push_back(segment);
+        }
+        while segments.len() > self.window + SEGMENTS_KEPT_AFTER_WINDOW {
+            segments.pop_front();
+        }
+    }
+
+    fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    fn counters(&self) -> &MountCounters {
+        &self.counters
+    }
+
+    fn restarted(&self) {
+        // os frames completos do segmento atual continuam valendo, num segmento só deles
+        self.segmenter.lock().unwrap().reset();
+    }
+}
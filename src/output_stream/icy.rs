@@ -0,0 +1,93 @@
+// metadados ICY (Shoutcast/Icecast) no meio do stream, para players como VLC, Winamp e rádios de carro
+
+use bytes::Bytes;
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// De quantos em quantos bytes de áudio mandamos um bloco de metadados
+pub const ICY_METAINT: usize = 16000;
+
+/// O bloco de metadados tem no máximo 255 * 16 bytes
+const MAX_METADATA_LENGTH: usize = 255 * 16;
+
+/// Se o cliente pediu metadados ICY (`Icy-MetaData: 1`)
+pub struct IcyMetadataRequest(pub bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IcyMetadataRequest {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let wants_metadata = request
+            .headers()
+            .get_one("Icy-MetaData")
+            .is_some_and(|value| value.trim() == "1");
+
+        Outcome::Success(IcyMetadataRequest(wants_metadata))
+    }
+}
+
+/// Conta os bytes de áudio enviados a um cliente e intercala um bloco de metadados a cada `ICY_METAINT` bytes.
+/// Cada cliente tem o seu, já que os chunks do broadcast são compartilhados mas o ponto de corte não.
+pub struct IcyInterleaver {
+    metaint: usize,
+    bytes_until_metadata: usize,
+    last_title: Option<String>,
+}
+
+impl IcyInterleaver {
+    pub fn new(metaint: usize) -> IcyInterleaver {
+        IcyInterleaver {
+            metaint,
+            bytes_until_metadata: metaint,
+            last_title: None,
+        }
+    }
+
+    /// Divide o chunk nos pontos de corte e insere os blocos de metadados. Os pedaços de áudio são fatias do
+    /// chunk original (sem cópia); só os blocos de metadados são alocados.
+    pub fn interleave(&mut self, mut chunk: Bytes, title: &str) -> Vec<Bytes> {
+        let mut parts = Vec::with_capacity(3);
+
+        while chunk.len() >= self.bytes_until_metadata {
+            parts.push(chunk.split_to(self.bytes_until_metadata));
+            parts.push(self.metadata_block(title));
+            self.bytes_until_metadata = self.metaint;
+        }
+
+        if !chunk.is_empty() {
+            self.bytes_until_metadata -= chunk.len();
+            parts.push(chunk);
+        }
+
+        parts
+    }
+
+    /// Um bloco só com o byte de tamanho zero significa "nada mudou"; o título é reenviado só quando troca
+    fn metadata_block(&mut self, title: &str) -> Bytes {
+        if self.last_title.as_deref() == Some(title) {
+            return Bytes::from_static(&[0]);
+        }
+        self.last_title = Some(title.to_owned());
+
+        // aspas simples terminariam o StreamTitle antes da hora em alguns players
+        let mut sanitized: String = title
+            .chars()
+            .map(|c| if c == '\'' { '’' } else { c })
+            .filter(|c| !c.is_control())
+            .collect();
+        // cortar em fronteira de caractere, para não mandar UTF-8 quebrado
+        while sanitized.len() + "StreamTitle='';".len() > MAX_METADATA_LENGTH {
+            sanitized.pop();
+        }
+        let mut metadata = format!("StreamTitle='{}';", sanitized).into_bytes();
+
+        // o tamanho é dado em blocos de 16 bytes, e o resto é preenchido com zeros
+        let blocks = metadata.len().div_ceil(16);
+        metadata.resize(blocks * 16, 0);
+
+        let mut block = Vec::with_capacity(1 + metadata.len());
+        block.push(blocks as u8);
+        block.extend_from_slice(&metadata);
+        Bytes::from(block)
+    }
+}
@@ -1,55 +1,237 @@
 use bytes::Bytes;
-use rocket::{http::ContentType, response::stream::ByteStream};
+use rocket::{
+    futures::Stream,
+    http::{ContentType, Header},
+    request::Request,
+    response::{self, stream::ByteStream, Responder},
+};
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
     },
     time::Instant,
 };
+use web_radio::objects::{
+    station::{
+        sessions::{SessionEnd, SessionLog, SessionRecord},
+        station::Station,
+    },
+    subscriber::{ByteCounter, Subscriber},
+};
+use tracing::{debug, error, info, trace, warn};
 use tokio::sync::{
     broadcast::{self as tbroadcast, error::RecvError},
     oneshot,
 };
 
+pub mod bans;
+pub mod bitrate;
+pub mod capacity;
+pub mod client;
+pub mod dash;
+pub mod flac;
+pub mod flood;
+pub mod hls;
+pub mod icy;
+pub mod negotiation;
+pub mod token;
+pub mod websocket;
+
 use crate::output_encoder::{
-    audio_encoder::OutputCodec,
+    audio_encoder::{EncodedOutput, OutputCodec},
     null_frames::{get_mime_type, get_null_frame},
 };
 
+use self::{
+    bitrate::BitrateMeter,
+    capacity::ListenerLimits,
+    client::ClientIdentity,
+    flood::ConnectionPermit,
+    icy::{IcyInterleaver, ICY_METAINT},
+    token::{StreamAccess, TokenError},
+};
+
+/// gera os IDs dos clients; é global para que o id seja único entre estações e codecs
+static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+
 /// guarda as info de cada cliente conectado
 struct ClientInfo {
-    shutdown_tx: oneshot::Sender<()>, // canal pra mandar o sinal de desligar
-    bytes_sent: Arc<AtomicUsize>,     // contador de bytes enviados (thread-safe)
+    shutdown_tx: oneshot::Sender<SessionEnd>, // canal pra mandar o sinal de desligar, com o motivo
+    bytes_sent: ByteCounter,          // contador de bytes enviados (thread-safe), o mesmo do Subscriber
     connected_at: Instant,            // quando o cliente conectou
 }
 
+/// Contadores de um mount desde que o servidor subiu, expostos em `/metrics`
+#[derive(Default)]
+pub struct MountCounters {
+    /// bytes enviados a todos os clientes, inclusive os que já saíram
+    pub bytes_sent: ByteCounter,
+    /// mensagens do canal de áudio puladas por clientes que ficaram para trás
+    pub lagged_messages: AtomicUsize,
+    /// vezes que o ffmpeg deste codec morreu e foi reiniciado
+    pub encoder_restarts: AtomicUsize,
+    /// o que sai do ffmpeg, medido, já que nem todo codec tem bitrate fixo
+    pub encoded: BitrateMeter,
+}
+
+/// Nome estável e legível de um mount, ex.: `diamondcityradio/mp3`; também é o caminho dele em `/station/`
+pub fn mount_name(station: &str, codec: &OutputCodec) -> String {
+    format!("{}/{}", station, codec.name())
+}
+
+/// Por que um mount recusou o cliente
+#[derive(Debug)]
+pub enum StreamRefused {
+    /// a estação (ou o codec) está cheia
+    Full,
+    /// token ausente numa estação privada, ou token inválido
+    Unauthorized(TokenError),
+}
+
+/// Por que o manifesto (playlist HLS ou MPD do DASH) ou um segmento não foi servido
+#[derive(Debug)]
+pub enum SegmentRefused {
+    /// token ausente numa estação privada, ou token inválido
+    Unauthorized(TokenError),
+    /// ainda não há segmentos suficientes
+    NotReady,
+    /// o segmento não existe ou já saiu da janela
+    UnknownSegment,
+}
+
+/// Manifesto ou segmento das saídas segmentadas. O manifesto muda a cada segmento, então não pode ficar em cache;
+/// os segmentos nunca mudam
+#[derive(Responder)]
+pub struct SegmentResponse {
+    pub body: Vec<u8>,
+    pub content_type: ContentType,
+    pub cache_control: Header<'static>,
+}
+
+/// Resposta HTTP de um stream de áudio: o corpo em streaming, mais o content-type e os headers do mount
+pub struct StreamResponse<S> {
+    pub content_type: ContentType,
+    pub headers: Vec<Header<'static>>,
+    pub stream: ByteStream<S>,
+}
+
+impl<'r, S> Responder<'r, 'r> for StreamResponse<S>
+where
+    S: Stream<Item = Bytes> + Send + 'r,
+{
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let mut response = self.stream.respond_to(request)?;
+        response.set_header(self.content_type);
+        for header in self.headers {
+            response.set_header(header);
+        }
+        Ok(response)
+    }
+}
+
 pub struct OutputStream {
     // codec de audio que a gente usa
     codec: OutputCodec,
+    // nome do mount depois da estação e nos registros: o nome do codec, ou `ws` no WebSocket
+    name: &'static str,
+    // nome do mount (estação/codec), enviado nos headers de cada cliente
+    mount: String,
+    // nome da estação, para o header icy-name
+    station: String,
     // canal pra distribuir o audio pros clients
     tx: tbroadcast::Sender<Bytes>,
     // mapa de clientes ativos
     clients: Arc<Mutex<HashMap<usize, ClientInfo>>>,
-    // gera os IDs únicos pros clients
-    next_id: AtomicUsize,
+    // estação onde cada cliente é registrado como Subscriber enquanto estiver conectado
+    registry: Arc<Mutex<Station>>,
+    // quantos ouvintes cabem na estação e neste codec
+    limits: ListenerLimits,
+    // como este mount trata as URLs assinadas
+    access: StreamAccess,
+    // "Artista - Título" da faixa no ar, enviado nos metadados ICY
+    stream_title: Arc<RwLock<String>>,
+    // cabeçalho do stream gerado pelo ffmpeg, para os codecs sem frame null (FLAC)
+    stream_header: Arc<RwLock<Option<Bytes>>>,
+    // total de conexões da rádio inteira (`Radio.connections`)
+    connections: Arc<AtomicUsize>,
+    counters: Arc<MountCounters>,
+    // onde cada sessão é gravada quando o cliente sai
+    sessions: Arc<SessionLog>,
 }
 
 impl OutputStream {
     /// cria um novo stream manager
-    pub fn new(codec: OutputCodec) -> OutputStream {
+    pub fn new(
+        station: &str,
+        codec: OutputCodec,
+        registry: Arc<Mutex<Station>>,
+        limits: ListenerLimits,
+        access: StreamAccess,
+        connections: Arc<AtomicUsize>,
+        sessions: Arc<SessionLog>,
+    ) -> OutputStream {
         // canal com buffer de 24 mensagens
         // TODO: mexer nesse valor até ficar razoável. capacidade de 24 aguentou 301 clientes no meu PC
         let (tx, _) = tbroadcast::channel::<Bytes>(24);
         OutputStream {
+            mount: mount_name(station, &codec),
+            station: station.to_owned(),
+            name: codec.name(),
             codec,
             tx,
             clients: Arc::new(Mutex::new(HashMap::new())),
-            next_id: AtomicUsize::new(0),
+            registry,
+            limits,
+            access,
+            stream_title: Arc::new(RwLock::new(String::new())),
+            stream_header: Arc::new(RwLock::new(None)),
+            connections,
+            counters: Arc::default(),
+            sessions,
         }
     }
 
+    /// Troca o nome do mount, para saídas que não são um stream HTTP do codec, ex.: `estação/ws`
+    pub fn renamed(mut self, name: &'static str) -> OutputStream {
+        self.name = name;
+        self.mount = format!("{}/{}", self.station, name);
+        self
+    }
+
+    pub fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    pub fn codec(&self) -> &OutputCodec {
+        &self.codec
+    }
+
+    /// O codec, ou o nome dado em `renamed`; é o que aparece como codec nas sessões, na API e em `/metrics`
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// "Artista - Título" da faixa no ar
+    pub fn stream_title(&self) -> String {
+        self.stream_title.read().unwrap().clone()
+    }
+
+    pub fn counters(&self) -> &MountCounters {
+        &self.counters
+    }
+
+    /// Atualiza o título enviado aos clientes que pediram metadados ICY
+    pub fn set_stream_title(&self, title: String) {
+        *self.stream_title.write().unwrap() = title;
+    }
+
+    /// Guarda o cabeçalho que os clientes recebem antes do áudio, no lugar do frame null
+    pub fn set_stream_header(&self, header: Bytes) {
+        *self.stream_header.write().unwrap() = Some(header);
+    }
+
     /// Manda audio pra todos os clientes conectados
     pub fn push(&self, packet: Bytes) {
         let _ = self.tx.send(packet);
@@ -57,14 +239,31 @@ impl OutputStream {
         // (se não tiver ninguém ouvindo, não tem problema, nada vai ocorrer)
     }
 
-    /// Remover um cliente específico pelo ID
+    /// Remover um cliente específico pelo ID; a sessão dele fica registrada como `kicked`
     pub fn terminate_client(&self, id: usize) {
         if let Some(info) = self.clients.lock().unwrap().remove(&id) {
             // sinalizar que a stream deve ser droppada
-            let _ = info.shutdown_tx.send(());
-            eprintln!("server: cliente {} foi removido", id);
+            let _ = info.shutdown_tx.send(SessionEnd::Kicked);
+            info!(
+                station = %self.station,
+                codec = self.name,
+                client_id = id,
+                "cliente removido"
+            );
         } else {
-            eprintln!("server: tentou matar o cliente {} que nem existe", id);
+            warn!(
+                station = %self.station,
+                codec = self.name,
+                client_id = id,
+                "tentou remover um cliente que nem existe"
+            );
+        }
+    }
+
+    /// Encerra o stream de todos os clientes, ex.: quando o servidor vai desligar; as sessões ficam como `normal`
+    pub fn close_all(&self) {
+        for (_, info) in self.clients.lock().unwrap().drain() {
+            let _ = info.shutdown_tx.send(SessionEnd::Normal);
         }
     }
 
@@ -76,7 +275,7 @@ impl OutputStream {
         clients
             .iter()
             .map(|(id, info)| {
-                let bytes = info.bytes_sent.load(Ordering::Relaxed); // bytes totais
+                let bytes = info.bytes_sent.get(); // bytes totais
                 let tempo = now.duration_since(info.connected_at).as_secs_f64();
                 // calcula os bits por segundo (bps)
                 let bps = if tempo > 0.0 {
@@ -89,127 +288,360 @@ impl OutputStream {
             .collect()
     }
 
+    /// Quantos clientes estão conectados agora
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
     /// Lista os IDs de todos os clients conectados
     pub fn list_clients(&self) -> Vec<usize> {
         self.clients.lock().unwrap().keys().copied().collect()
     }
 
-    /// Cria um novo stream de audio pra um cliente
-    pub fn create_consumer_http_stream(&self) -> (ContentType, ByteStream![Bytes]) {
+    /// Aceita um cliente, se ainda couber ouvinte na estação e no mount e o token dele, se houver ou se a estação
+    /// for privada, for válido. Clientes privilegiados (admin, ou token com ouvinte) podem ocupar as vagas reservadas.
+    /// O cliente fica registrado até a `Admission` ser solta
+    /// Se um ouvinte sem token e sem privilégio entraria agora; é o que o fallback de outra estação precisa
+    pub fn accepts_anonymous(&self) -> bool {
+        if self.access.private {
+            return false;
+        }
+        let station = self.registry.lock().unwrap();
+        self.limits
+            .admits(station._subscribers.len(), self.codec_listeners(&station), false)
+    }
+
+    fn codec_listeners(&self, station: &Station) -> usize {
+        station
+            ._subscribers
+            .iter()
+            .filter(|subscriber| subscriber.codec == self.name)
+            .count()
+    }
+
+    pub fn admit(&self, client: ClientIdentity, permit: ConnectionPermit) -> Result<Admission, StreamRefused> {
         // pega um ID novo pro cliente
-        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+
+        let claims = self
+            .access
+            .check(client.token.as_deref(), &self.station)
+            .map_err(|e| {
+                info!(
+                    station = %self.station,
+                    codec = self.name,
+                    client_id = id,
+                    "cliente recusado: {}",
+                    e
+                );
+                StreamRefused::Unauthorized(e)
+            })?;
+        let listener_id = claims.and_then(|claims| claims.listener);
+        let privileged = client.privileged || listener_id.is_some();
+
         // canal pra mandar o sinal de desligar
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let mut subscriber = Subscriber::new(
+            id,
+            client.remote_address,
+            client.user_agent,
+            self.name.to_owned(),
+        );
+        subscriber.listener_id = listener_id;
         // contador de bytes enviados
-        let bytes_sent = Arc::new(AtomicUsize::new(0));
+        let bytes_sent = subscriber.bytes_sent.clone();
+
+        // a contagem e o registro acontecem com a estação travada, então dois clientes não pegam a mesma última vaga
+        {
+            let mut station = self.registry.lock().unwrap();
+            let codec_listeners = self.codec_listeners(&station);
+            if !self
+                .limits
+                .admits(station._subscribers.len(), codec_listeners, privileged)
+            {
+                info!(
+                    station = %self.station,
+                    codec = self.name,
+                    client_id = id,
+                    "mount lotado, cliente recusado"
+                );
+                return Err(StreamRefused::Full);
+            }
+            station.add_subscriber(subscriber.clone());
+        }
+        info!(
+            station = %self.station,
+            codec = self.name,
+            client_id = id,
+            address = ?subscriber.remote_address,
+            listener = ?subscriber.listener_id,
+            "cliente conectado"
+        );
 
         // registra o cliente no mapa
         self.clients.lock().unwrap().insert(
             id,
             ClientInfo {
                 shutdown_tx,
-                bytes_sent: Arc::clone(&bytes_sent),
+                bytes_sent: bytes_sent.clone(),
                 connected_at: Instant::now(), // marca o horário que conectou
-            },
+            }
         );
+        self.connections.fetch_add(1, Ordering::Relaxed);
 
-        let codec = self.codec.clone();
-        let mut rx = self.tx.subscribe(); // cria um receptor pro canal de audio
-
-        // flag pra saber se terminou normalmente
-        let normal_exit = Arc::new(AtomicBool::new(false));
-        let exit_flag = Arc::clone(&normal_exit);
-        let clients = Arc::clone(&self.clients);
-
-        /// guardião que limpa tudo quando o stream acaba
-        struct CleanupGuard {
-            clients: Arc<Mutex<HashMap<usize, ClientInfo>>>,
-            id: usize,
-            exit_flag: Arc<AtomicBool>,
-            bytes_sent: Arc<AtomicUsize>,
-        }
+        Ok(Admission {
+            id,
+            rx: self.tx.subscribe(), // cria um receptor pro canal de audio
+            shutdown_rx,
+            counters: Arc::clone(&self.counters),
+            // cria um guard pra esse cliente, executado quando a admissão é solta
+            guard: CleanupGuard {
+                clients: Arc::clone(&self.clients),
+                station: self.station.clone(),
+                codec: self.name,
+                id,
+                end_reason: None,
+                bytes_sent,
+                connections: Arc::clone(&self.connections),
+                registry: Arc::clone(&self.registry),
+                subscriber,
+                sessions: Arc::clone(&self.sessions),
+                _permit: permit,
+            },
+        })
+    }
 
-        impl Drop for CleanupGuard {
-            fn drop(&mut self) {
-                if !self.exit_flag.load(Ordering::SeqCst) {
-                    eprintln!(
-                        "server({}): cliente caiu - enviou {} bytes no total",
-                        self.id,
-                        self.bytes_sent.load(Ordering::Relaxed)
-                    );
-                }
-                // remove o cliente do mapa automaticamente
-                self.clients.lock().unwrap().remove(&self.id);
-            }
-        }
+    /// Cria um novo stream de audio pra um cliente aceito por `admit`.
+    /// Com `icy_metadata`, intercala blocos de metadados ICY (StreamTitle) a cada `ICY_METAINT` bytes de áudio
+    pub fn create_consumer_http_stream(
+        &self,
+        client: ClientIdentity,
+        permit: ConnectionPermit,
+        icy_metadata: bool,
+    ) -> Result<StreamResponse<impl Stream<Item = Bytes>>, StreamRefused> {
+        let mut admission = self.admit(client, permit)?;
+        let id = admission.id;
 
-        // cria um guard pra esse stream, executado quando a stream deve ser droppada
-        let guard = CleanupGuard {
-            clients,
-            id,
-            exit_flag: exit_flag.clone(),
-            bytes_sent: Arc::clone(&bytes_sent),
-        };
+        let codec = self.codec.clone();
+        let stream_title = Arc::clone(&self.stream_title);
+        let stream_header = Arc::clone(&self.stream_header);
+        let mut icy = icy_metadata.then(|| IcyInterleaver::new(ICY_METAINT));
+        let station = self.station.clone();
 
         let stream = ByteStream! {
-            // mover receiver que fica ouvindo o sinal de desligar, e o guard da stream, pra cá
-            let mut shutdown_rx = shutdown_rx;
-            let _guard = guard;
-
-            // manda o frame null inicial
-            let null_frame = Bytes::from(get_null_frame(&codec));
-            let null_size = null_frame.len();
-            bytes_sent.fetch_add(null_size, Ordering::Relaxed);
-            yield null_frame;
-            eprintln!(
-                "server({}): mandou frame null ({} bytes) para o cliente",
-                id, null_size
-            );
+            // manda o começo do stream: o frame null do codec ou, no FLAC, o cabeçalho gerado pelo ffmpeg
+            let start = match get_null_frame(&codec) {
+                Some(null_frame) => Some(Bytes::from_static(null_frame)),
+                None => stream_header.read().unwrap().clone(),
+            };
+            // o ffmpeg ainda não gerou o cabeçalho: ele é guardado antes do primeiro frame, então vai junto dele
+            let mut header_pending = start.is_none();
+            if let Some(start) = start {
+                let start_size = start.len();
+                match icy.as_mut() {
+                    Some(icy) => {
+                        let title = stream_title.read().unwrap().clone();
+                        for part in icy.interleave(start, &title) {
+                            admission.sent(part.len());
+                            yield part;
+                        }
+                    }
+                    None => {
+                        admission.sent(start_size);
+                        yield start;
+                    }
+                }
+                trace!(
+                    station = %station,
+                    codec = codec.name(),
+                    client_id = id,
+                    bytes = start_size,
+                    "mandou o começo do stream para o cliente"
+                );
+            }
 
-            'receive: loop {
-                tokio::select! {
-                    // receber o próximo pacote de dados
-                    result = rx.recv() => {
-                        match result {
-                            Ok(chunk) => {
-                                let size = chunk.len();
-                                bytes_sent.fetch_add(size, Ordering::Relaxed);  // atualiza contador de I/O
-                                yield chunk;
-                            }
-                            Err(err) => match err {
-                                RecvError::Lagged(n) => {
-                                    eprintln!(
-                                        "server({}): cliente ficou {} mensagens atrasado - skip!",
-                                        id, n
-                                    );
-                                },
-
-                                // isso ocorre quando não há mais Sender para o canal, mas jamais deverá ocorrer na aplicação, já que as estações são permanentes e singletons
-                                RecvError::Closed =>
-                                    panic!("server({}): o canal de broadcast fechou do nada!", id)
-                            },
+            // receber o próximo pacote de dados, até o cliente ser derrubado
+            while let Some(mut chunk) = admission.recv().await {
+                if header_pending {
+                    header_pending = false;
+                    if let Some(header) = stream_header.read().unwrap().as_ref() {
+                        chunk = Bytes::from([header.as_ref(), chunk.as_ref()].concat());
+                    }
+                }
+                match icy.as_mut() {
+                    // com ICY, cada cliente tem seu próprio ponto de corte dos metadados
+                    Some(icy) => {
+                        let title = stream_title.read().unwrap().clone();
+                        for part in icy.interleave(chunk, &title) {
+                            admission.sent(part.len());
+                            yield part;
                         }
                     }
-                    // aguardar o sinal de desligar
-                    _ = &mut shutdown_rx => {
-                        eprintln!("server({}): sinal de shutdown para o cliente", id);
-                        break 'receive;
+                    None => {
+                        admission.sent(chunk.len()); // atualiza contador de I/O
+                        yield chunk;
                     }
                 }
             }
 
-            // marcar que terminou normalmente
-            normal_exit.store(true, Ordering::SeqCst);
-            let total_bytes = bytes_sent.load(Ordering::Relaxed);
-            eprintln!(
-                "server({}): stream cliente acabou ({} bytes no total)",
-                id, total_bytes
+            debug!(
+                station = %station,
+                codec = codec.name(),
+                client_id = id,
+                bytes_sent = admission.bytes_sent(),
+                "stream do cliente acabou"
             );
         };
 
-        (
-            ContentType::new("audio", get_mime_type(&self.codec)),
+        let mut headers = vec![
+            Header::new("icy-name", self.station.clone()),
+            Header::new("X-Mount", self.mount.clone()),
+        ];
+        if icy_metadata {
+            headers.push(Header::new("icy-metaint", ICY_METAINT.to_string()));
+        }
+
+        Ok(StreamResponse {
+            content_type: ContentType::new("audio", get_mime_type(&self.codec)),
+            headers,
             stream,
-        )
+        })
+    }
+}
+
+/// Um cliente aceito por um mount: recebe o áudio do canal até ser derrubado e, ao ser solto (o cliente caiu ou
+/// terminou), sai do mount e da estação e tem a sessão gravada
+pub struct Admission {
+    pub id: usize,
+    rx: tbroadcast::Receiver<Bytes>,
+    shutdown_rx: oneshot::Receiver<SessionEnd>,
+    counters: Arc<MountCounters>,
+    guard: CleanupGuard,
+}
+
+impl Admission {
+    /// Próximo pacote de áudio; `None` quando o cliente foi derrubado ou o servidor está desligando
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        if self.guard.end_reason.is_some() {
+            return None;
+        }
+
+        loop {
+            tokio::select! {
+                result = self.rx.recv() => match result {
+                    Ok(chunk) => return Some(chunk),
+                    Err(RecvError::Lagged(n)) => {
+                        self.counters.lagged_messages.fetch_add(n as usize, Ordering::Relaxed);
+                        debug!(
+                            station = %self.guard.station,
+                            codec = self.guard.codec,
+                            client_id = self.id,
+                            skipped = n,
+                            "cliente ficou atrasado - skip!"
+                        );
+                    }
+                    // isso ocorre quando não há mais Sender para o canal, mas jamais deverá ocorrer na aplicação, já que as estações são permanentes e singletons
+                    Err(RecvError::Closed) => panic!("server({}): o canal de broadcast fechou do nada!", self.id),
+                },
+                // aguardar o sinal de desligar
+                reason = &mut self.shutdown_rx => {
+                    // sem motivo (o sender sumiu), conta como se o cliente tivesse caído
+                    self.guard.end_reason = Some(reason.unwrap_or(SessionEnd::Dropped));
+                    debug!(
+                        station = %self.guard.station,
+                        codec = self.guard.codec,
+                        client_id = self.id,
+                        "sinal de shutdown para o cliente"
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Conta `bytes` entregues ao cliente, na sessão dele e no mount
+    pub fn sent(&self, bytes: usize) {
+        self.guard.bytes_sent.add(bytes);
+        self.counters.bytes_sent.add(bytes);
+    }
+
+    pub fn bytes_sent(&self) -> usize {
+        self.guard.bytes_sent.get()
+    }
+
+    /// Por que o cliente foi derrubado, depois que `recv` devolveu `None`
+    pub fn end_reason(&self) -> Option<SessionEnd> {
+        self.guard.end_reason
+    }
+}
+
+/// guardião que limpa tudo quando o cliente sai
+struct CleanupGuard {
+    clients: Arc<Mutex<HashMap<usize, ClientInfo>>>,
+    station: String,
+    codec: &'static str,
+    id: usize,
+    // por que o cliente saiu; continua `None` se ele caiu
+    end_reason: Option<SessionEnd>,
+    bytes_sent: ByteCounter,
+    connections: Arc<AtomicUsize>,
+    registry: Arc<Mutex<Station>>,
+    subscriber: Subscriber,
+    sessions: Arc<SessionLog>,
+    // solta a vaga do endereço no controle de flood
+    _permit: ConnectionPermit,
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        if self.end_reason.is_none() {
+            debug!(
+                station = %self.station,
+                codec = self.codec,
+                client_id = self.id,
+                bytes_sent = self.bytes_sent.get(),
+                "cliente caiu"
+            );
+        }
+        // remove o cliente do mapa automaticamente
+        self.clients.lock().unwrap().remove(&self.id);
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+        self.registry.lock().unwrap().remove_subscriber(&self.subscriber);
+
+        let record = SessionRecord::ended_now(
+            &self.station,
+            &self.subscriber,
+            self.end_reason.unwrap_or(SessionEnd::Dropped),
+        );
+        let sessions = Arc::clone(&self.sessions);
+        let (station, codec, id) = (self.station.clone(), self.codec, self.id);
+        let append = move || {
+            if let Err(e) = sessions.append(&record) {
+                error!(station = %station, codec, client_id = id, "{}", e);
+            }
+        };
+        // gravar no arquivo bloqueia: dentro do runtime, vai para as threads de I/O do tokio em vez de segurar
+        // um worker; fora dele (ex.: a thread do WebSocket), grava ali mesmo
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(append);
+            }
+            Err(_) => append(),
+        }
+    }
+}
+
+impl EncodedOutput for OutputStream {
+    fn push(&self, packet: Bytes) {
+        OutputStream::push(self, packet);
+    }
+
+    fn mount(&self) -> &str {
+        OutputStream::mount(self)
+    }
+
+    fn counters(&self) -> &MountCounters {
+        OutputStream::counters(self)
     }
 }
@@ -0,0 +1,138 @@
+// escolha do codec de saída para quem pede só `/station/<nome>`, sem dizer o codec na URL
+
+use rocket::{
+    http::MediaType,
+    request::{FromRequest, Outcome, Request},
+};
+
+use crate::output_encoder::{audio_encoder::OutputCodec, null_frames::get_mime_type};
+
+/// O que o cliente disse (direta ou indiretamente) sobre os formatos que ele toca
+pub struct ClientHints {
+    pub accept: Vec<(MediaType, f32)>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientHints {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let accept = request
+            .accept()
+            .map(|accept| {
+                accept
+                    .iter()
+                    .map(|media| (media.media_type().clone(), media.weight_or(1.0)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Outcome::Success(ClientHints {
+            accept,
+            user_agent: request.headers().get_one("User-Agent").map(str::to_owned),
+        })
+    }
+}
+
+/// Escolhe o codec na ordem: `?format=`, header `Accept`, limitações conhecidas do User-Agent e, por fim, o codec padrão da estação.
+/// `offered` está na ordem de preferência da estação. Retorna `Err` se `format` pedir um codec que a estação não tem.
+pub fn negotiate_codec(
+    offered: &[OutputCodec],
+    default_codec: &OutputCodec,
+    format: Option<&str>,
+    hints: &ClientHints,
+) -> Result<OutputCodec, String> {
+    if let Some(format) = format {
+        // o nome do codec primeiro: `?format=aac` é o AAC-LC, mesmo que um HE-AAC (também `audio/aac`) venha antes
+        return offered
+            .iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(format))
+            .or_else(|| {
+                offered
+                    .iter()
+                    .find(|codec| get_mime_type(codec).eq_ignore_ascii_case(format))
+            })
+            .cloned()
+            .ok_or_else(|| format!("a estação não transmite em {}", format));
+    }
+
+    if let Some(codec) = codec_from_accept(offered, &hints.accept) {
+        return Ok(codec);
+    }
+
+    let user_agent = hints.user_agent.as_deref().unwrap_or("");
+    if user_agent_plays(user_agent, default_codec) {
+        return Ok(default_codec.clone());
+    }
+
+    Ok(offered
+        .iter()
+        .find(|codec| user_agent_plays(user_agent, codec))
+        .unwrap_or(default_codec)
+        .clone())
+}
+
+/// Só tipos explícitos contam; `*/*` e `audio/*` (o que os navegadores mandam pro `<audio>`) não dizem nada
+fn codec_from_accept(offered: &[OutputCodec], accept: &[(MediaType, f32)]) -> Option<OutputCodec> {
+    let mut ranked: Vec<&(MediaType, f32)> = accept
+        .iter()
+        .filter(|(media, weight)| media.sub() != "*" && *weight > 0.0)
+        .collect();
+    // sort estável: empates de q mantêm a ordem do header
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranked.iter().find_map(|(media, _)| {
+        offered
+            .iter()
+            .find(|codec| accepts_codec(media, codec))
+            .cloned()
+    })
+}
+
+fn accepts_codec(media: &MediaType, codec: &OutputCodec) -> bool {
+    if media.top() != "audio" {
+        return false;
+    }
+
+    // audio/ogg; codecs=opus também vale para o stream opus, que é encapsulado em Ogg
+    let ogg_opus = media.sub() == "ogg"
+        && media
+            .params()
+            .any(|(key, value)| key == "codecs" && value.eq_ignore_ascii_case("opus"));
+
+    match codec {
+        OutputCodec::Mp3_64kbps => media.sub() == "mpeg" || media.sub() == "mp3",
+        OutputCodec::Ogg96kbps => media.sub() == "ogg" && !ogg_opus,
+        OutputCodec::Opus128kbps => media.sub() == "opus" || ogg_opus,
+        OutputCodec::Aac128kbps => media.sub() == "aac" || media.sub() == "x-aac",
+        // audio/aacp é o nome antigo do HE-AAC (aacPlus), ainda usado por players de rádio
+        OutputCodec::HeAac48kbps | OutputCodec::HeAacV2_32kbps => {
+            media.sub() == "aac" || media.sub() == "x-aac" || media.sub() == "aacp"
+        }
+        OutputCodec::Flac => media.sub() == "flac" || media.sub() == "x-flac",
+    }
+}
+
+/// Heurística para clientes que sabidamente não tocam Ogg: Safari/iOS e players clássicos. AAC em ADTS toca em todos;
+/// FLAC toca no Safari, mas não nos players clássicos
+fn user_agent_plays(user_agent: &str, codec: &OutputCodec) -> bool {
+    let is_apple_webkit = user_agent.contains("AppleCoreMedia")
+        || user_agent.contains("iTunes")
+        || (user_agent.contains("Safari")
+            && !user_agent.contains("Chrome")
+            && !user_agent.contains("Chromium")
+            && !user_agent.contains("Edg"));
+    let is_classic_player = ["Winamp", "NSPlayer", "WinampMPEG", "Windows-Media-Player"]
+        .iter()
+        .any(|player| user_agent.contains(player));
+
+    match codec {
+        OutputCodec::Mp3_64kbps
+        | OutputCodec::Aac128kbps
+        | OutputCodec::HeAac48kbps
+        | OutputCodec::HeAacV2_32kbps => true,
+        OutputCodec::Ogg96kbps | OutputCodec::Opus128kbps => !is_apple_webkit && !is_classic_player,
+        OutputCodec::Flac => !is_classic_player,
+    }
+}
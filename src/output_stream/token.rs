@@ -0,0 +1,135 @@
+// URLs assinadas para os streams: um token HMAC-SHA256 com a estação, a validade e, opcionalmente, o ouvinte.
+// Estações privadas só aceitam clientes com token; nas outras, um token válido só identifica o ouvinte
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// O que vai assinado dentro do token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamClaims {
+    pub station: String,
+    /// segundos desde a época unix
+    pub expires_at: u64,
+    pub listener: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Missing,
+    Malformed,
+    BadSignature,
+    WrongStation,
+    Expired,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Missing => write!(f, "token: esta estação exige ?token="),
+            TokenError::Malformed => write!(f, "token: mal formado"),
+            TokenError::BadSignature => write!(f, "token: assinatura inválida"),
+            TokenError::WrongStation => write!(f, "token: emitido para outra estação"),
+            TokenError::Expired => write!(f, "token: expirado"),
+        }
+    }
+}
+
+/// Assina e confere tokens com o segredo da configuração
+#[derive(Clone)]
+pub struct StreamSigner {
+    secret: Vec<u8>,
+}
+
+impl StreamSigner {
+    pub fn new(secret: &str) -> Self {
+        StreamSigner {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC aceita chave de qualquer tamanho")
+    }
+
+    /// Token no formato `<claims em base64url>.<assinatura em base64url>`, válido por `ttl`
+    pub fn sign(&self, station: &str, ttl: Duration, listener: Option<String>) -> (String, StreamClaims) {
+        let expires_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_add(ttl)
+            .as_secs();
+        let claims = StreamClaims {
+            station: station.to_owned(),
+            expires_at,
+            listener,
+        };
+
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        (format!("{}.{}", payload, signature), claims)
+    }
+
+    /// Confere a assinatura (em tempo constante), a estação e a validade
+    pub fn verify(&self, token: &str, station: &str) -> Result<StreamClaims, TokenError> {
+        let (payload, signature) = token.split_once('.').ok_or(TokenError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| TokenError::Malformed)?;
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| TokenError::BadSignature)?;
+
+        let claims: StreamClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(TokenError::Malformed)?;
+
+        if claims.station != station {
+            return Err(TokenError::WrongStation);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if claims.expires_at <= now {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Como um mount trata tokens: sem `signer` (nenhum segredo configurado), tokens são ignorados
+#[derive(Clone, Default)]
+pub struct StreamAccess {
+    pub signer: Option<StreamSigner>,
+    /// só aceita clientes com token válido
+    pub private: bool,
+}
+
+impl StreamAccess {
+    /// Os claims do token, se houver um válido. Em estação privada, token ausente ou inválido é erro;
+    /// nas outras, um token inválido também é recusado, para não passar despercebido
+    pub fn check(&self, token: Option<&str>, station: &str) -> Result<Option<StreamClaims>, TokenError> {
+        match (token, &self.signer) {
+            (Some(token), Some(signer)) => signer.verify(token, station).map(Some),
+            _ if self.private => Err(TokenError::Missing),
+            _ => Ok(None),
+        }
+    }
+}
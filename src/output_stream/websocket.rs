@@ -0,0 +1,395 @@
+// WebSocket de baixa latência: pacotes Opus soltos, cada um com número de sequência e timestamp, para players
+// com Web Audio / WebCodecs. O ffmpeg gera Ogg Opus com uma página por pacote; aqui as páginas viram pacotes,
+// e os pacotes são liberados aos clientes no ritmo do relógio, já que o PCM chega aos encoders adiantado
+//
+// Protocolo (uma conexão em /station/<nome>/ws):
+// - mensagens de texto são JSON com um campo `type`:
+//   - `config`, a primeira mensagem: `codec` ("opus"), `sample_rate` (48000), `channels` (2), `header_bytes` (16),
+//     `description` (o OpusHead em base64, para o `AudioDecoder.configure`) e `title`, a faixa no ar
+//   - `title`, quando a faixa no ar muda: `title`
+// - mensagens binárias são um pacote Opus cada, depois de um cabeçalho de 16 bytes, big-endian:
+//   - bytes 0..4: número de sequência (u32), +1 por pacote; um salto indica pacotes perdidos por atraso
+//   - bytes 4..12: timestamp em microssegundos (u64) desde que a saída começou, contínuo mesmo se o ffmpeg reiniciar
+//   - bytes 12..16: duração do pacote em microssegundos (u32)
+// - o cliente não precisa mandar nada; qualquer mensagem dele é ignorada, e um Close encerra a sessão
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use rocket::{
+    data::{IoHandler, IoStream},
+    futures::{SinkExt, StreamExt},
+    request::{self, FromRequest, Outcome, Request},
+    response::{self, Responder, Response},
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::derive_accept_key,
+        protocol::{frame::coding::CloseCode, CloseFrame, Role},
+        Message,
+    },
+    WebSocketStream,
+};
+use tracing::{debug, info_span, warn};
+use web_radio::objects::station::sessions::SessionEnd;
+
+use crate::output_encoder::audio_encoder::{ffmpeg_command, EncodedOutput};
+
+use super::{Admission, MountCounters, OutputStream};
+
+/// Opus decodifica sempre a 48 kHz, qualquer que seja a taxa da entrada
+const OPUS_SAMPLE_RATE: u64 = 48_000;
+pub const FRAME_HEADER_BYTES: usize = 16;
+/// bitrate padrão: o suficiente para música em estéreo, pensando em quem liga do celular
+pub const DEFAULT_BITRATE_KBPS: u32 = 64;
+
+/// Ogg Opus com pacotes de 20 ms e uma página por pacote, para que cada pacote saia do ffmpeg assim que fica pronto
+pub fn ffmpeg_args(bitrate_kbps: u32) -> Vec<String> {
+    let bitrate = format!("{}k", bitrate_kbps);
+
+    ffmpeg_command(&[
+        "-c:a",
+        "libopus",
+        "-b:a",
+        &bitrate,
+        "-application",
+        "lowdelay",
+        "-frame_duration",
+        "20",
+        "-f",
+        "ogg",
+        "-page_duration",
+        "20000",
+        "-flush_packets",
+        "1",
+    ])
+}
+
+/// Junta os pacotes de um stream Ogg com um só stream lógico, página por página
+#[derive(Default)]
+struct OggPackets {
+    /// bytes que ainda não formam uma página inteira
+    pending: Vec<u8>,
+    /// pacote que continua na próxima página
+    packet: Vec<u8>,
+}
+
+impl OggPackets {
+    fn push(&mut self, bytes: &[u8]) -> Vec<Bytes> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut packets = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = &self.pending[offset..];
+            if page.len() < 27 {
+                break;
+            }
+            if !page.starts_with(b"OggS") {
+                // fora de sincronia: pula até a próxima página
+                match page.windows(4).skip(1).position(|window| window == b"OggS") {
+                    Some(position) => offset += position + 1,
+                    None => offset = self.pending.len() - 3,
+                }
+                self.packet.clear();
+                continue;
+            }
+
+            let segment_count = page[26] as usize;
+            let Some(lacing) = page.get(27..27 + segment_count) else {
+                break;
+            };
+            let body_len: usize = lacing.iter().map(|&len| len as usize).sum();
+            if page.len() < 27 + segment_count + body_len {
+                break;
+            }
+
+            // sem a flag de continuação, o que sobrou da página anterior não vai ser completado
+            if page[5] & 0x01 == 0 {
+                self.packet.clear();
+            }
+            let mut at = 27 + segment_count;
+            for &len in lacing {
+                self.packet.extend_from_slice(&page[at..at + len as usize]);
+                at += len as usize;
+                // um segmento menor que 255 fecha o pacote
+                if len < 255 {
+                    packets.push(Bytes::from(std::mem::take(&mut self.packet)));
+                }
+            }
+            offset += at;
+        }
+
+        self.pending.drain(..offset);
+        packets
+    }
+}
+
+/// Quantas amostras (a 48 kHz) um pacote Opus tem, pelo byte TOC (RFC 6716, seção 3.1)
+fn opus_packet_samples(packet: &[u8]) -> u64 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+
+    let config = toc >> 3;
+    // em 1/400 s: 2,5 ms, 5 ms, 10 ms...
+    let frame_units: u64 = match config {
+        0..=11 => [4, 8, 16, 24][config as usize % 4],
+        12..=15 => [4, 8][config as usize % 2],
+        _ => [1, 2, 4, 8][config as usize % 4],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |&count| (count & 0x3F) as u64),
+    };
+
+    frames * frame_units * OPUS_SAMPLE_RATE / 400
+}
+
+/// Onde o próximo pacote entra na linha do tempo da saída
+#[derive(Default)]
+struct Framer {
+    ogg: OggPackets,
+    sequence: u32,
+    /// amostras já emitidas, a 48 kHz
+    samples: u64,
+}
+
+/// Um pacote pronto, esperando a hora de sair
+struct PacedFrame {
+    timestamp: Duration,
+    frame: Bytes,
+}
+
+/// A saída WebSocket de uma estação: recebe Ogg Opus do seu próprio ffmpeg e entrega os pacotes, emoldurados,
+/// aos clientes do mount `estação/ws`, que conta ouvintes e sessões como os streams HTTP
+pub struct WebSocketOutput {
+    stream: Arc<OutputStream>,
+    framer: Mutex<Framer>,
+    /// o cabeçalho OpusHead do ffmpeg atual; os clientes o recebem na mensagem `config`
+    head: RwLock<Option<Bytes>>,
+    pacer: Mutex<Sender<PacedFrame>>,
+}
+
+impl WebSocketOutput {
+    /// `stream` é o mount onde os clientes são registrados
+    pub fn new(stream: OutputStream) -> WebSocketOutput {
+        let stream = Arc::new(stream);
+        let (pacer, frames) = mpsc::channel::<PacedFrame>();
+
+        // libera cada pacote no instante do seu timestamp, contado a partir do primeiro; um pacote que chega
+        // atrasado (o ffmpeg reiniciou, a estação voltou a bufferizar) sai na hora e vira a nova referência
+        let paced = Arc::clone(&stream);
+        let span = info_span!("websocket", mount = paced.mount());
+        thread::spawn(move || {
+            let _span = span.entered();
+            let mut anchor: Option<Instant> = None;
+            for PacedFrame { timestamp, frame } in frames {
+                let now = Instant::now();
+                let due = anchor.map(|anchor| anchor + timestamp).filter(|due| *due >= now);
+                match due {
+                    Some(due) => thread::sleep(due - now),
+                    None => {
+                        if anchor.is_some() {
+                            debug!(timestamp_us = timestamp.as_micros() as u64, "pacote atrasado, reancorando");
+                        }
+                        anchor = Some(now - timestamp);
+                    }
+                }
+                paced.push(frame);
+            }
+        });
+
+        WebSocketOutput {
+            stream,
+            framer: Mutex::new(Framer::default()),
+            head: RwLock::new(None),
+            pacer: Mutex::new(pacer),
+        }
+    }
+
+    pub fn stream(&self) -> &Arc<OutputStream> {
+        &self.stream
+    }
+
+    /// Abre uma sessão WebSocket para um cliente já aceito pelo mount
+    pub fn session(&self, admission: Admission, upgrade: WebSocketUpgrade) -> WebSocketSession {
+        WebSocketSession {
+            accept: derive_accept_key(upgrade.key.as_bytes()),
+            admission,
+            stream: Arc::clone(&self.stream),
+            head: self.head.read().unwrap().clone(),
+        }
+    }
+}
+
+impl EncodedOutput for WebSocketOutput {
+    fn push(&self, packet: Bytes) {
+        let mut framer = self.framer.lock().unwrap();
+        for packet in framer.ogg.push(&packet) {
+            if packet.starts_with(b"OpusHead") {
+                *self.head.write().unwrap() = Some(packet);
+                continue;
+            }
+            if packet.starts_with(b"OpusTags") {
+                continue;
+            }
+
+            let samples = opus_packet_samples(&packet);
+            let timestamp = framer.samples * 1_000_000 / OPUS_SAMPLE_RATE;
+            let duration = samples * 1_000_000 / OPUS_SAMPLE_RATE;
+
+            let mut frame = Vec::with_capacity(FRAME_HEADER_BYTES + packet.len());
+            frame.extend_from_slice(&framer.sequence.to_be_bytes());
+            frame.extend_from_slice(&timestamp.to_be_bytes());
+            frame.extend_from_slice(&(duration as u32).to_be_bytes());
+            frame.extend_from_slice(&packet);
+
+            framer.sequence = framer.sequence.wrapping_add(1);
+            framer.samples += samples;
+            // a thread do pacer só morre junto com o processo
+            let _ = self.pacer.lock().unwrap().send(PacedFrame {
+                timestamp: Duration::from_micros(timestamp),
+                frame: Bytes::from(frame),
+            });
+        }
+    }
+
+    fn mount(&self) -> &str {
+        self.stream.mount()
+    }
+
+    fn counters(&self) -> &MountCounters {
+        self.stream.counters()
+    }
+
+    fn restarted(&self) {
+        self.framer.lock().unwrap().ogg = OggPackets::default();
+    }
+}
+
+/// O pedido de upgrade para WebSocket (RFC 6455): `Upgrade: websocket` e a chave do cliente
+pub struct WebSocketUpgrade {
+    key: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebSocketUpgrade {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        let headers = request.headers();
+        let upgrade = headers
+            .get("Upgrade")
+            .any(|value| value.split(',').any(|protocol| protocol.trim().eq_ignore_ascii_case("websocket")));
+        let version = headers.get_one("Sec-WebSocket-Version") == Some("13");
+
+        match headers.get_one("Sec-WebSocket-Key") {
+            Some(key) if upgrade && version => Outcome::Success(WebSocketUpgrade { key: key.to_owned() }),
+            _ => Outcome::Forward(rocket::http::Status::UpgradeRequired),
+        }
+    }
+}
+
+/// A resposta 101 e, depois dela, a conexão de um cliente até ele sair ou ser derrubado
+pub struct WebSocketSession {
+    accept: String,
+    admission: Admission,
+    stream: Arc<OutputStream>,
+    head: Option<Bytes>,
+}
+
+impl<'r> Responder<'r, 'static> for WebSocketSession {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .raw_header("Sec-WebSocket-Accept", self.accept.clone())
+            .upgrade("websocket", self)
+            .ok()
+    }
+}
+
+#[rocket::async_trait]
+impl IoHandler for WebSocketSession {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let WebSocketSession {
+            mut admission,
+            stream,
+            head,
+            ..
+        } = *Pin::into_inner(self);
+        let mut socket = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
+
+        let mut title = stream.stream_title();
+        let config = serde_json::json!({
+            "type": "config",
+            "codec": "opus",
+            "sample_rate": OPUS_SAMPLE_RATE,
+            "channels": 2,
+            "header_bytes": FRAME_HEADER_BYTES,
+            "description": head.map(|head| STANDARD.encode(head)),
+            "title": title,
+        });
+        if socket.send(Message::Text(config.to_string())).await.is_err() {
+            return Ok(());
+        }
+
+        loop {
+            tokio::select! {
+                frame = admission.recv() => {
+                    let Some(frame) = frame else {
+                        // derrubado pelo admin ou servidor desligando: avisa o cliente antes de fechar
+                        let (code, reason) = match admission.end_reason() {
+                            Some(SessionEnd::Kicked) => (CloseCode::Policy, "derrubado"),
+                            _ => (CloseCode::Away, "servidor desligando"),
+                        };
+                        let _ = socket.close(Some(CloseFrame { code, reason: reason.into() })).await;
+                        break;
+                    };
+
+                    let current = stream.stream_title();
+                    if current != title {
+                        title = current;
+                        let message = serde_json::json!({ "type": "title", "title": title });
+                        if socket.send(Message::Text(message.to_string())).await.is_err() {
+                            break;
+                        }
+                    }
+
+                    let size = frame.len();
+                    if socket.send(Message::Binary(frame.to_vec())).await.is_err() {
+                        break;
+                    }
+                    admission.sent(size);
+                }
+                message = socket.next() => match message {
+                    // pings são respondidos pelo tungstenite; o resto é ignorado
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        warn!(client_id = admission.id, "erro no WebSocket: {}", e);
+                        break;
+                    }
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+
+        debug!(
+            mount = stream.mount(),
+            client_id = admission.id,
+            bytes_sent = admission.bytes_sent(),
+            "WebSocket do cliente acabou"
+        );
+        Ok(())
+    }
+}
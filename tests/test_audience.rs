@@ -0,0 +1,31 @@
+#[cfg(test)]
+pub mod tests_audience {
+    use std::fs;
+
+    use web_radio::objects::track::audience::{AudienceBook, RankBy};
+
+    #[test]
+    fn test_audience_ranking_and_persistence() {
+        let path = std::env::temp_dir().join(format!("web_radio_audience_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut audience = AudienceBook::load(&path).unwrap();
+        audience.record_play("a.mp3", 10, 2.0).unwrap();
+        audience.record_play("a.mp3", 4, 4.0).unwrap();
+        audience.record_play("b.mp3", 6, 5.0).unwrap();
+
+        let reloaded = AudienceBook::load(&path).unwrap();
+        let a = reloaded.get("a.mp3").unwrap();
+        assert_eq!(a.plays, 2);
+        assert_eq!(a.peak_listeners, 10);
+        assert_eq!(a.last_peak_listeners, 4);
+        assert_eq!(a.average_listeners(), 3.0);
+
+        let by_peak: Vec<&str> = reloaded.ranking(RankBy::Peak).into_iter().map(|(source, _)| source).collect();
+        assert_eq!(by_peak, vec!["a.mp3", "b.mp3"]);
+        let by_average: Vec<&str> = reloaded.ranking(RankBy::Average).into_iter().map(|(source, _)| source).collect();
+        assert_eq!(by_average, vec!["b.mp3", "a.mp3"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
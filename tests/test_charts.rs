@@ -0,0 +1,53 @@
+#[cfg(test)]
+pub mod tests_charts {
+    use std::fs;
+
+    use web_radio::objects::station::likes::LikeBook;
+    use web_radio::objects::track::charts::{sort_chart, station_chart, ChartBy, PlayLog, PlayRecord};
+
+    fn play(source: &str, started_at: i64, peak_listeners: usize, average_listeners: f64) -> PlayRecord {
+        PlayRecord {
+            source: source.to_owned(),
+            started_at,
+            seconds: 1800.0,
+            peak_listeners,
+            average_listeners,
+        }
+    }
+
+    #[test]
+    fn test_station_chart() {
+        let dir = std::env::temp_dir().join(format!("web_radio_charts_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let log = PlayLog::new(&dir.join("plays.jsonl"));
+        log.append(&play("a.mp3", 100, 10, 4.0)).unwrap();
+        log.append(&play("a.mp3", 200, 2, 2.0)).unwrap();
+        log.append(&play("b.mp3", 300, 5, 5.0)).unwrap();
+        log.append(&play("c.mp3", 10_000, 50, 50.0)).unwrap();
+
+        let mut likes = LikeBook::load(&dir.join("likes.json")).unwrap();
+        likes.like_track("b.mp3", "ouvinte-1").unwrap();
+
+        // a janela corta a execução de c.mp3; os likes acabaram de ser dados, então a janela vai até "agora"
+        let plays = log.range(0, 1_000).unwrap();
+        let mut chart = station_chart("radio", &plays, &likes, 0, i64::MAX);
+        assert_eq!(chart.len(), 2);
+
+        sort_chart(&mut chart, ChartBy::Plays);
+        assert_eq!(chart[0].source, "a.mp3");
+        assert_eq!(chart[0].plays, 2);
+        assert_eq!(chart[0].peak_listeners, 10);
+        assert_eq!(chart[0].listener_hours, 3.0);
+
+        sort_chart(&mut chart, ChartBy::Likes);
+        assert_eq!(chart[0].source, "b.mp3");
+        assert_eq!(chart[0].likes, 1);
+
+        sort_chart(&mut chart, ChartBy::ListenerHours);
+        assert_eq!(chart[0].source, "a.mp3");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+pub mod tests_config {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    /// Escreve o config num diretório próprio, com as pastas das estações `a`, `b` e `c`, e sobe o servidor com
+    /// ele; um config inválido encerra o processo antes de abrir a porta. Devolve a saída, com os erros
+    fn config_errors(test: &str, yaml: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("web_radio_config_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        for station in ["a", "b", "c"] {
+            fs::create_dir_all(directory.join(station)).unwrap();
+            fs::write(directory.join(station).join("metadata.json"), "[]").unwrap();
+        }
+        let path: PathBuf = directory.join("config.yaml");
+        fs::write(&path, yaml).unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_web-radio"))
+            .env("WEB_RADIO_CONFIG", &path)
+            .output()
+            .unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(!output.status.success(), "config deveria ser recusado:\n{}", yaml);
+        let mut errors = String::from_utf8_lossy(&output.stdout).into_owned();
+        errors += &String::from_utf8_lossy(&output.stderr);
+        assert!(errors.contains("configuração inválida"), "{}", errors);
+        errors
+    }
+
+    fn station(name: &str, extra: &str) -> String {
+        format!(
+            "  - name: {}\n    directory: ./{}\n    frequency: 98.9\n    codecs: [mp3, ogg]\n{}",
+            name, name, extra
+        )
+    }
+
+    #[test]
+    fn test_config_unknown_keys() {
+        let yaml = format!("bogus: 1\nstations:\n{}", station("a", "    volume: 11\n"));
+        let errors = config_errors("unknown_keys", &yaml);
+
+        assert!(errors.contains("'bogus': chave desconhecida"), "{}", errors);
+        assert!(errors.contains("stations[0].'volume': chave desconhecida"), "{}", errors);
+    }
+
+    #[test]
+    fn test_config_numbers_out_of_range() {
+        let yaml = format!(
+            "flood:\n  ban_minutes: 999999999\nstations:\n{}",
+            station(
+                "a",
+                "    max_listeners: 0\n    buffer: { setpoint_high: 2, setpoint_low: 5 }\n    hls: { codec: aac, segment_seconds: 600 }\n"
+            )
+        )
+        .replace("codecs: [mp3, ogg]", "codecs:\n      - codec: mp3\n        bitrate: 4\n      - ogg");
+        let errors = config_errors("ranges", &yaml);
+
+        assert!(errors.contains("flood.ban_minutes: 999999999 acima do máximo"), "{}", errors);
+        assert!(errors.contains("stations[0].max_listeners: deve ser maior que zero"), "{}", errors);
+        assert!(errors.contains("stations[0].buffer: setpoint_low (5) deve ser menor que setpoint_high (2)"), "{}", errors);
+        assert!(errors.contains("stations[0].hls.segment_seconds: 600 fora do intervalo"), "{}", errors);
+        assert!(errors.contains("stations[0].codecs[0].bitrate: 4 kbps fora do intervalo"), "{}", errors);
+    }
+
+    #[test]
+    fn test_config_numbers_that_do_not_fit_are_not_truncated() {
+        // 4294967360 truncado para u32 daria 64 kbps, que passaria na checagem de intervalo
+        let yaml = format!("stations:\n{}", station("a", ""))
+            .replace("codecs: [mp3, ogg]", "codecs:\n      - codec: mp3\n        bitrate: 4294967360");
+        let errors = config_errors("wrapping", &yaml);
+
+        assert!(errors.contains("stations[0].codecs[0].bitrate: 4294967360 é grande demais"), "{}", errors);
+    }
+
+    #[test]
+    fn test_config_frequency_must_be_finite_and_positive() {
+        for (test, frequency) in [("nan", ".nan"), ("inf", ".inf"), ("zero", "0"), ("huge", "1e300")] {
+            let yaml = format!("stations:\n{}", station("a", "")).replace("98.9", frequency);
+            let errors = config_errors(&format!("frequency_{}", test), &yaml);
+
+            assert!(errors.contains("stations[0].frequency: deve ser um número positivo"), "{}: {}", frequency, errors);
+        }
+    }
+
+    #[test]
+    fn test_config_duplicate_mounts() {
+        let yaml = format!("stations:\n{}{}", station("a", ""), station("a", ""))
+            .replacen("codecs: [mp3, ogg]", "codecs: [mp3, mp3]", 1);
+        let errors = config_errors("duplicates", &yaml);
+
+        assert!(errors.contains("stations: estação a declarada mais de uma vez"), "{}", errors);
+        assert!(errors.contains("stations[0].codecs[1].codec: codec mp3 repetido"), "{}", errors);
+    }
+
+    #[test]
+    fn test_config_fallback_errors() {
+        let yaml = format!(
+            "stations:\n{}{}{}",
+            station("a", "    fallback_mount: a/mp3\n"),
+            station("b", "    fallback_mount: c/opus\n"),
+            station("c", "    fallback_mount: nowhere/mp3\n"),
+        );
+        let errors = config_errors("fallback", &yaml);
+
+        assert!(errors.contains("stations[0].fallback_mount: deve ser de outra estação"), "{}", errors);
+        assert!(errors.contains("stations[1].fallback_mount: 'c/opus' não é um mount conhecido"), "{}", errors);
+        assert!(errors.contains("stations[2].fallback_mount: 'nowhere/mp3' não é um mount conhecido"), "{}", errors);
+    }
+
+    #[test]
+    fn test_config_fallback_cycles() {
+        let yaml = format!(
+            "stations:\n{}{}{}",
+            station("a", "    fallback_mount: b/mp3\n"),
+            station("b", "    fallback_mount: c/ogg\n"),
+            station("c", "    fallback_mount: a/mp3\n"),
+        );
+        let errors = config_errors("cycle", &yaml);
+
+        assert!(errors.contains("stations[0].fallback_mount: ciclo de fallback (a -> b -> c -> a)"), "{}", errors);
+        assert!(errors.contains("stations[2].fallback_mount: ciclo de fallback (c -> a -> b -> c)"), "{}", errors);
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(test)]
+pub mod tests_history {
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use rocket::time::{Date, Month};
+    use web_radio::objects::station::history::{day_bounds, StationHistory};
+    use web_radio::objects::station::station::Station;
+    use web_radio::objects::station::station_state::MockStationState;
+    use web_radio::objects::subscriber::Subscriber;
+    use web_radio::objects::track::track::Track;
+
+    #[test]
+    fn test_history_day_query() {
+        let path = std::env::temp_dir().join(format!("web_radio_history_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let track = Track::new("Title".to_owned(), "Artist".to_owned(), "Album".to_owned(), 60, "mp3".to_owned(), "a.mp3".to_owned(), vec![], vec![]);
+        let station = Station::with_tracks("Diamond City Radio".to_owned(), "./".to_owned(), 98.9, Box::new(MockStationState::new()), vec![track.clone()]);
+        let history = StationHistory::new(&path);
+
+        // 2025-03-01 23:59:59 UTC e 2025-03-02 00:00:00 UTC
+        let (march_2, _) = day_bounds(Date::from_calendar_date(2025, Month::March, 2).unwrap());
+        history.append(&station.snapshot(&track, 3, UNIX_EPOCH + Duration::from_secs(march_2 as u64 - 1))).unwrap();
+        history.append(&station.snapshot(&track, 7, UNIX_EPOCH + Duration::from_secs(march_2 as u64))).unwrap();
+
+        let day = history.day(Date::from_calendar_date(2025, Month::March, 2).unwrap()).unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!(day[0].listeners, 7);
+        assert_eq!(day[0].name, "Diamond City Radio");
+        assert_eq!(day[0].current_track.title, "Title");
+        assert_eq!(day[0].created_on(), Date::from_calendar_date(2025, Month::March, 2).unwrap());
+
+        assert_eq!(history.range(0, i64::MAX).unwrap().len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_history_keeps_no_listener_data() {
+        let path = std::env::temp_dir().join(format!("web_radio_history_private_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let track = Track::new("Title".to_owned(), "Artist".to_owned(), "Album".to_owned(), 60, "mp3".to_owned(), "a.mp3".to_owned(), vec![], vec![]);
+        let mut station = Station::with_tracks("Diamond City Radio".to_owned(), "./".to_owned(), 98.9, Box::new(MockStationState::new()), vec![track.clone()]);
+        station.add_subscriber(Subscriber::new(1, Some("203.0.113.7".parse().unwrap()), Some("VLC/3.0".to_owned()), "mp3".to_owned()));
+        let history = StationHistory::new(&path);
+        history.append(&station.snapshot(&track, 1, UNIX_EPOCH + Duration::from_secs(60))).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(!written.contains("203.0.113.7"));
+        assert!(!written.contains("VLC"));
+
+        // linhas gravadas antes, com a lista de ouvintes, continuam sendo lidas
+        let old_line = written.trim_end().replacen('{', "{\"subscribers\":[{\"id\":1}],", 1);
+        fs::write(&path, old_line + "\n").unwrap();
+        let snapshots = history.range(0, i64::MAX).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].listeners, 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+#[cfg(test)]
+pub mod tests_json_store {
+    use std::{collections::BTreeMap, fs};
+
+    use web_radio::objects::json_store::{load_json_or_default, save_json_atomically};
+
+    #[test]
+    fn test_json_store_round_trip() {
+        let path = std::env::temp_dir().join(format!("web_radio_json_store_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // sem arquivo, começa do padrão
+        let empty: BTreeMap<String, u64> = load_json_or_default(&path).unwrap();
+        assert!(empty.is_empty());
+
+        let mut plays = BTreeMap::new();
+        plays.insert("a.mp3".to_owned(), 3u64);
+        save_json_atomically(&path, &plays).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let loaded: BTreeMap<String, u64> = load_json_or_default(&path).unwrap();
+        assert_eq!(loaded, plays);
+
+        fs::write(&path, "{ pela metade").unwrap();
+        assert!(load_json_or_default::<BTreeMap<String, u64>>(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
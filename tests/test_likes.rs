@@ -0,0 +1,49 @@
+#[cfg(test)]
+pub mod tests_likes {
+    use std::fs;
+
+    use web_radio::objects::station::likes::LikeBook;
+
+    #[test]
+    fn test_likes_are_deduplicated_and_persisted() {
+        let path = std::env::temp_dir().join(format!("web_radio_likes_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut likes = LikeBook::load(&path).unwrap();
+        assert!(likes.like_track("a.mp3", "ouvinte-1").unwrap());
+        assert!(!likes.like_track("a.mp3", "ouvinte-1").unwrap());
+        assert!(likes.like_track("a.mp3", "ouvinte-2").unwrap());
+        assert!(likes.like_station("ouvinte-1").unwrap());
+        assert!(!likes.like_station("ouvinte-1").unwrap());
+
+        let reloaded = LikeBook::load(&path).unwrap();
+        assert_eq!(reloaded.track_likes("a.mp3"), 2);
+        assert_eq!(reloaded.track_likes("b.mp3"), 0);
+        assert_eq!(reloaded.station_likes(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_likes_without_timestamps_still_load() {
+        let path = std::env::temp_dir().join(format!("web_radio_likes_old_{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"station":["ouvinte-1","ouvinte-2"],"tracks":{"a.mp3":["ouvinte-1"]}}"#,
+        )
+        .unwrap();
+
+        let mut likes = LikeBook::load(&path).unwrap();
+        assert_eq!(likes.station_likes(), 2);
+        assert_eq!(likes.track_likes("a.mp3"), 1);
+        // os likes antigos contam como dados no instante 0
+        assert_eq!(likes.track_likes_between(0, 1).get("a.mp3"), Some(&1));
+        assert!(!likes.like_track("a.mp3", "ouvinte-1").unwrap());
+        assert!(likes.like_track("a.mp3", "ouvinte-2").unwrap());
+
+        let reloaded = LikeBook::load(&path).unwrap();
+        assert_eq!(reloaded.track_likes("a.mp3"), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+pub mod tests_playlist {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use web_radio::objects::track::playlist;
+
+    #[test]
+    fn test_import_m3u_with_extinf() {
+        let dir = playlist_dir("m3u");
+        fs::write(dir.join("a.mp3"), b"").unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/b.ogg"), b"").unwrap();
+        fs::write(
+            dir.join("list.m3u8"),
+            "#EXTM3U\n#EXTINF:215,Artist A - Title A\na.mp3\n\n#EXTINF:-1,Untitled\nsub/b.ogg\nmissing.mp3\n",
+        )
+        .unwrap();
+
+        let import = playlist::import(&dir.join("list.m3u8")).unwrap();
+
+        assert_eq!(import.tracks.len(), 2);
+        assert_eq!(import.tracks[0].artist, "Artist A");
+        assert_eq!(import.tracks[0].title, "Title A");
+        assert_eq!(import.tracks[0].duration, 215);
+        assert_eq!(import.tracks[0].source, dir.join("a.mp3").to_string_lossy());
+        assert_eq!(import.tracks[1].title, "Untitled");
+        assert_eq!(import.tracks[1].duration, 0);
+        assert_eq!(import.tracks[1].file_format, "ogg");
+
+        assert_eq!(import.missing.len(), 1);
+        assert_eq!(import.missing[0].position, 3);
+        assert_eq!(import.missing[0].resolved, Some(dir.join("missing.mp3")));
+    }
+
+    #[test]
+    fn test_import_pls() {
+        let dir = playlist_dir("pls");
+        fs::write(dir.join("a.mp3"), b"").unwrap();
+        fs::write(
+            dir.join("list.pls"),
+            "[playlist]\nFile2=http://example.com/stream\nFile1=a.mp3\nTitle1=Artist - Song\nLength1=61\nNumberOfEntries=2\nVersion=2\n",
+        )
+        .unwrap();
+
+        let import = playlist::import(&dir.join("list.pls")).unwrap();
+
+        assert_eq!(import.tracks.len(), 1);
+        assert_eq!(import.tracks[0].title, "Song");
+        assert_eq!(import.tracks[0].duration, 61);
+        assert_eq!(import.missing.len(), 1);
+        assert_eq!(import.missing[0].position, 2);
+        assert_eq!(import.missing[0].resolved, None);
+    }
+
+    #[test]
+    fn test_import_xspf() {
+        let dir = playlist_dir("xspf");
+        fs::write(dir.join("a b.flac"), b"").unwrap();
+        fs::write(
+            dir.join("list.xspf"),
+            format!(
+                "<?xml version=\"1.0\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\"><trackList>\n<track><location>file://{}/a%20b.flac</location><title>Rock &amp; Roll</title><creator>Band</creator><album>LP</album><duration>123456</duration></track>\n</trackList></playlist>",
+                dir.display()
+            ),
+        )
+        .unwrap();
+
+        let import = playlist::import(&dir.join("list.xspf")).unwrap();
+
+        assert!(import.missing.is_empty());
+        assert_eq!(import.tracks.len(), 1);
+        assert_eq!(import.tracks[0].title, "Rock & Roll");
+        assert_eq!(import.tracks[0].artist, "Band");
+        assert_eq!(import.tracks[0].album, "LP");
+        assert_eq!(import.tracks[0].duration, 123);
+    }
+
+    fn playlist_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("web_radio_playlist_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
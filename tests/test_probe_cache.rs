@@ -0,0 +1,140 @@
+// o audio_file_info.rs só depende de crates externos e da lib, então é compilado aqui dentro mesmo
+#[allow(dead_code)]
+#[path = "../src/audio_file_info.rs"]
+mod audio_file_info;
+
+#[cfg(test)]
+pub mod tests_probe_cache {
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::audio_file_info::{AudioFileInfo, ProbeCache};
+
+    /// Diretório com `a.mp3` e `b.mp3`, e o caminho do cache dentro dele
+    fn library(test: &str) -> (PathBuf, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("web_radio_probe_cache_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.mp3"), [0u8; 100]).unwrap();
+        fs::write(directory.join("b.mp3"), [0u8; 200]).unwrap();
+        let cache = directory.join(".probe_cache.json");
+        (directory, cache)
+    }
+
+    /// Probe de mentira: 1 segundo por 100 bytes, contando quantas vezes foi chamado
+    fn query(cache: &mut ProbeCache, location: &Path, probes: &Cell<usize>) -> AudioFileInfo {
+        cache
+            .query_with(location.to_path_buf(), |location| {
+                probes.set(probes.get() + 1);
+                let size_bytes = fs::metadata(&location).unwrap().len();
+                Ok(serde_json::from_value(serde_json::json!({
+                    "location": location,
+                    "size_bytes": size_bytes,
+                    "audio_milliseconds": size_bytes * 10,
+                }))
+                .unwrap())
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_probe_cache_hit() {
+        let (directory, cache_path) = library("hit");
+        let a = directory.join("a.mp3");
+        let probes = Cell::new(0);
+
+        let mut cache = ProbeCache::load(cache_path.clone());
+        let info = query(&mut cache, &a, &probes);
+        assert_eq!(info.audio_milliseconds(), 1000);
+        assert_eq!(query(&mut cache, &a, &probes), info);
+        assert_eq!(probes.get(), 1);
+
+        // e continua valendo depois de salvo e recarregado
+        cache.save().unwrap();
+        let mut cache = ProbeCache::load(cache_path);
+        assert_eq!(query(&mut cache, &a, &probes), info);
+        assert_eq!(probes.get(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_probe_cache_file_changed_on_disk() {
+        let (directory, cache_path) = library("changed");
+        let a = directory.join("a.mp3");
+        let probes = Cell::new(0);
+        let mut cache = ProbeCache::load(cache_path);
+        query(&mut cache, &a, &probes);
+
+        // outro tamanho
+        fs::write(&a, [0u8; 300]).unwrap();
+        assert_eq!(query(&mut cache, &a, &probes).audio_milliseconds(), 3000);
+        assert_eq!(probes.get(), 2);
+
+        // mesmo tamanho, outro mtime
+        let modified = fs::metadata(&a).unwrap().modified().unwrap();
+        File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(modified - Duration::from_secs(60))
+            .unwrap();
+        query(&mut cache, &a, &probes);
+        assert_eq!(probes.get(), 3);
+        query(&mut cache, &a, &probes);
+        assert_eq!(probes.get(), 3);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_probe_cache_retain_drops_stale_entries() {
+        let (directory, cache_path) = library("retain");
+        let (a, b) = (directory.join("a.mp3"), directory.join("b.mp3"));
+        let probes = Cell::new(0);
+        let mut cache = ProbeCache::load(cache_path.clone());
+        query(&mut cache, &a, &probes);
+        query(&mut cache, &b, &probes);
+
+        cache.retain(&HashSet::from([a.clone()]));
+        cache.save().unwrap();
+        let saved = fs::read_to_string(&cache_path).unwrap();
+        assert!(saved.contains("a.mp3"));
+        assert!(!saved.contains("b.mp3"));
+
+        let mut cache = ProbeCache::load(cache_path);
+        query(&mut cache, &a, &probes);
+        assert_eq!(probes.get(), 2);
+        query(&mut cache, &b, &probes);
+        assert_eq!(probes.get(), 3);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_probe_cache_missing_or_corrupt_file() {
+        let (directory, cache_path) = library("corrupt");
+        let a = directory.join("a.mp3");
+        let probes = Cell::new(0);
+
+        // sem arquivo: cache vazio, e nada é gravado se nada mudou
+        let mut cache = ProbeCache::load(cache_path.clone());
+        cache.save().unwrap();
+        assert!(!cache_path.exists());
+        query(&mut cache, &a, &probes);
+        assert_eq!(probes.get(), 1);
+
+        // corrompido: começa vazio e é regravado inteiro no próximo save
+        fs::write(&cache_path, "{ pela metade").unwrap();
+        let mut cache = ProbeCache::load(cache_path.clone());
+        query(&mut cache, &a, &probes);
+        assert_eq!(probes.get(), 2);
+        cache.save().unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&cache_path).unwrap()).is_ok());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,21 @@
+#[cfg(test)]
+pub mod tests_radio {
+    use web_radio::objects::radio::Radio;
+    use web_radio::objects::station::station::Station;
+    use web_radio::objects::station::station_state::{Buffering, OnAir};
+
+    #[test]
+    fn test_add_station() {
+        let mut radio = Radio::new(1337, String::new());
+        radio.add_station(Station::with_tracks("zeta".to_owned(), "./zeta".to_owned(), 101.5, Box::new(Buffering), Vec::new()));
+        let station = radio.add_station(Station::with_tracks("alpha".to_owned(), "./alpha".to_owned(), 98.9, Box::new(Buffering), Vec::new()));
+
+        assert_eq!(radio.station_names(), vec!["alpha", "zeta"]);
+        assert_eq!(radio.connection_count(), 0);
+
+        // a referência devolvida é a mesma que o Radio guarda
+        station.lock().unwrap().change_state(Box::new(OnAir));
+        assert_eq!(radio.stations["alpha"].lock().unwrap().state_name(), "on_air");
+        assert_eq!(radio.stations["zeta"].lock().unwrap().state_name(), "buffering");
+    }
+}
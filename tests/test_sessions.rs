@@ -0,0 +1,59 @@
+#[cfg(test)]
+pub mod tests_sessions {
+    use std::fs;
+
+    use web_radio::objects::station::sessions::{
+        daily_report, report_csv, sessions_csv, SessionEnd, SessionLog, SessionRecord,
+    };
+
+    fn session(started_at: i64, seconds: i64, address: &str, listener_id: Option<&str>) -> SessionRecord {
+        SessionRecord {
+            station: "radio".to_owned(),
+            codec: "mp3".to_owned(),
+            started_at,
+            ended_at: started_at + seconds,
+            bytes_sent: 1000,
+            remote_address: Some(address.parse().unwrap()),
+            user_agent: Some("VLC/3.0, libvlc".to_owned()),
+            listener_id: listener_id.map(str::to_owned),
+            end_reason: SessionEnd::Dropped,
+        }
+    }
+
+    #[test]
+    fn test_session_report() {
+        let path = std::env::temp_dir().join(format!("web_radio_sessions_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // 2024-01-01 00:00:00 UTC
+        let day = 1_704_067_200;
+        let log = SessionLog::new(&path);
+        log.append(&session(day + 60, 3600, "10.0.0.1", None)).unwrap();
+        log.append(&session(day + 7200, 1800, "10.0.0.1", None)).unwrap();
+        // mesmo endereço, mas com token: outro ouvinte
+        log.append(&session(day + 9000, 1800, "10.0.0.1", Some("ana"))).unwrap();
+        log.append(&session(day + 86_400, 900, "10.0.0.2", None)).unwrap();
+
+        let sessions = log.range(day, day + 2 * 86_400).unwrap();
+        assert_eq!(sessions.len(), 4);
+
+        let report = daily_report(&sessions);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].day, "2024-01-01");
+        assert_eq!(report[0].sessions, 3);
+        assert_eq!(report[0].listening_hours, 2.0);
+        assert_eq!(report[0].average_session_seconds, 2400.0);
+        assert_eq!(report[0].unique_listeners, 2);
+        assert_eq!(report[1].day, "2024-01-02");
+        assert_eq!(report[1].unique_listeners, 1);
+
+        let csv = sessions_csv(&sessions[..1]);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "radio,mp3,1704067260,1704070860,3600,1000,10.0.0.1,\"VLC/3.0, libvlc\",,dropped"
+        );
+        assert_eq!(report_csv(&report).lines().nth(1).unwrap(), "2024-01-01,3,2.000,2400.0,2");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
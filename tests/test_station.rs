@@ -20,7 +20,7 @@ pub mod test_station {
     fn test_add_subscriber() {
         let mut station = get_station_test();
 
-        let subscriber = Subscriber{};
+        let subscriber = Subscriber::new(1, None, None, "mp3".to_owned());
         station.add_subscriber(subscriber.clone());
 
         assert_eq!(station._subscribers.len(), 1);
@@ -32,7 +32,7 @@ pub mod test_station {
         let mut station = get_station_test();
 
 
-        let subscriber = Subscriber{};
+        let subscriber = Subscriber::new(1, None, None, "mp3".to_owned());
         station.add_subscriber(subscriber.clone());
         station.remove_subscriber(&subscriber);
 
@@ -0,0 +1,106 @@
+// o token.rs só depende de crates externos, então é compilado aqui dentro mesmo, sem passar pelo binário
+#[path = "../src/output_stream/token.rs"]
+mod token;
+
+#[cfg(test)]
+pub mod tests_token {
+    use std::time::Duration;
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+    use super::token::{StreamAccess, StreamSigner, TokenError};
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn signer() -> StreamSigner {
+        StreamSigner::new("segredo-bem-comprido-123")
+    }
+
+    #[test]
+    fn test_token_valid() {
+        let (token, claims) = signer().sign("diamondcityradio", HOUR, Some("ouvinte-7".to_owned()));
+
+        let verified = signer().verify(&token, "diamondcityradio").unwrap();
+        assert_eq!(verified.station, "diamondcityradio");
+        assert_eq!(verified.listener.as_deref(), Some("ouvinte-7"));
+        assert_eq!(verified.expires_at, claims.expires_at);
+    }
+
+    #[test]
+    fn test_token_expired() {
+        let (token, _) = signer().sign("diamondcityradio", Duration::ZERO, None);
+
+        assert_eq!(signer().verify(&token, "diamondcityradio").unwrap_err(), TokenError::Expired);
+    }
+
+    #[test]
+    fn test_token_for_another_station() {
+        let (token, _) = signer().sign("galaxy", HOUR, None);
+
+        assert_eq!(signer().verify(&token, "diamondcityradio").unwrap_err(), TokenError::WrongStation);
+    }
+
+    #[test]
+    fn test_token_tampered() {
+        let (token, _) = signer().sign("galaxy", HOUR, None);
+        let (payload, signature) = token.split_once('.').unwrap();
+
+        // outro segredo
+        let other = StreamSigner::new("outro-segredo-comprido");
+        assert_eq!(other.verify(&token, "galaxy").unwrap_err(), TokenError::BadSignature);
+
+        // claims trocados, com a assinatura original
+        let forged = URL_SAFE_NO_PAD.encode(br#"{"station":"diamondcityradio","expires_at":99999999999,"listener":null}"#);
+        let forged = format!("{}.{}", forged, signature);
+        assert_eq!(signer().verify(&forged, "diamondcityradio").unwrap_err(), TokenError::BadSignature);
+
+        // assinatura com um byte trocado
+        let mut bytes = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        bytes[0] ^= 0x01;
+        let flipped = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(bytes));
+        assert_eq!(signer().verify(&flipped, "galaxy").unwrap_err(), TokenError::BadSignature);
+    }
+
+    #[test]
+    fn test_token_malformed() {
+        for token in ["", "sem-ponto", "payload.!!!", "a.b.c"] {
+            assert_eq!(signer().verify(token, "galaxy").unwrap_err(), TokenError::Malformed, "{}", token);
+        }
+        // assinatura vazia é base64 válido, só não confere
+        assert_eq!(signer().verify(".", "galaxy").unwrap_err(), TokenError::BadSignature);
+
+        // assinado direito, mas o conteúdo não é JSON de claims
+        let payload = URL_SAFE_NO_PAD.encode(b"nada a ver");
+        let signature = signer_signature(&payload);
+        let token = format!("{}.{}", payload, signature);
+        assert_eq!(signer().verify(&token, "galaxy").unwrap_err(), TokenError::Malformed);
+    }
+
+    #[test]
+    fn test_token_access() {
+        let (token, _) = signer().sign("galaxy", HOUR, None);
+        let private = StreamAccess {
+            signer: Some(signer()),
+            private: true,
+        };
+        let public = StreamAccess {
+            signer: Some(signer()),
+            private: false,
+        };
+
+        assert_eq!(private.check(None, "galaxy").unwrap_err(), TokenError::Missing);
+        assert!(private.check(Some(&token), "galaxy").unwrap().is_some());
+        assert!(public.check(None, "galaxy").unwrap().is_none());
+        assert_eq!(public.check(Some("lixo"), "galaxy").unwrap_err(), TokenError::Malformed);
+    }
+
+    /// HMAC-SHA256 de `payload` com o segredo do `signer()`, em base64url
+    fn signer_signature(payload: &str) -> String {
+        use hmac::{Hmac, Mac};
+        use sha2::Sha256;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"segredo-bem-comprido-123").unwrap();
+        mac.update(payload.as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+}